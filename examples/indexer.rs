@@ -16,7 +16,7 @@ fn main() -> Result<()> {
     let text = unsafe { std::str::from_utf8_unchecked(&text_mmap) };
 
     let mut writer = BufWriter::new(File::create(index_filename)?);
-    IndexBuilder::new(&text)
+    IndexBuilder::new(text)
         .block_size(1024 * 1024 * 1024) // 1G
         .build_to_writer_native_endian(&mut writer)?;
     writer.flush()?;
@@ -20,7 +20,7 @@ fn main() -> Result<()> {
     let text = unsafe { std::str::from_utf8_unchecked(&text_mmap) };
 
     let index_mmap = unsafe { Mmap::map(&File::open(index_filename)?)? };
-    let index = Index::from_bytes(&text, &index_mmap)?;
+    let index = Index::from_bytes(text, &index_mmap)?;
 
     let highlighted = Style::new().bold().fg(Colour::Green);
 
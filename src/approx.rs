@@ -0,0 +1,257 @@
+use crate::{Index, MultiDocIndex};
+use std::ops::Range;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Distance {
+    Hamming,
+    Levenshtein,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ApproxMatch {
+    pub position: u32,
+    pub len: u32,
+    pub distance: u32,
+}
+
+impl<'a, 'b> Index<'a, 'b> {
+    /// Returns the closest match starting at each position whose distance to
+    /// `query` is at most `max_errors`, sorted by position.
+    ///
+    /// Distances are counted in characters. Among matches starting at the
+    /// same position, the one with the smallest distance and then the
+    /// shortest length is reported.
+    pub fn approx_positions(
+        &self,
+        query: &str,
+        max_errors: usize,
+        distance: Distance,
+    ) -> Vec<ApproxMatch> {
+        let query = query.chars().collect::<Vec<_>>();
+        if query.is_empty() {
+            return Vec::new();
+        }
+
+        let mut matches = Vec::new();
+        let range = 0..self.suffix_array().len();
+        match distance {
+            Distance::Hamming => self.search_hamming(range, 0, &query, 0, max_errors, &mut matches),
+            Distance::Levenshtein => {
+                let row = (0..=query.len()).collect::<Vec<_>>();
+                self.search_levenshtein(range, 0, &query, &row, max_errors, &mut matches);
+            }
+        }
+
+        matches.sort_unstable_by_key(|m| (m.position, m.distance, m.len));
+        matches.dedup_by_key(|m| m.position);
+        matches
+    }
+
+    fn search_hamming(
+        &self,
+        range: Range<usize>,
+        depth: usize,
+        query: &[char],
+        errors: usize,
+        max_errors: usize,
+        matches: &mut Vec<ApproxMatch>,
+    ) {
+        let (q, rest) = match query.split_first() {
+            Some(x) => x,
+            None => {
+                self.report(range, depth, errors, matches);
+                return;
+            }
+        };
+        for (c, child) in self.children(range, depth) {
+            let errors = errors + (c != *q) as usize;
+            if errors <= max_errors {
+                let depth = depth + c.len_utf8();
+                self.search_hamming(child, depth, rest, errors, max_errors, matches);
+            }
+        }
+    }
+
+    // `row[j]` is the edit distance between the first `j` characters of `query`
+    // and the `depth` bytes shared by the suffixes in `range`.
+    fn search_levenshtein(
+        &self,
+        range: Range<usize>,
+        depth: usize,
+        query: &[char],
+        row: &[usize],
+        max_errors: usize,
+        matches: &mut Vec<ApproxMatch>,
+    ) {
+        for (c, child) in self.children(range, depth) {
+            let mut next = Vec::with_capacity(row.len());
+            next.push(row[0] + 1);
+            for (j, q) in query.iter().enumerate() {
+                let d = (row[j] + (c != *q) as usize)
+                    .min(row[j + 1] + 1)
+                    .min(next[j] + 1);
+                next.push(d);
+            }
+
+            let depth = depth + c.len_utf8();
+            let d = next[query.len()];
+            if d <= max_errors {
+                self.report(child.clone(), depth, d, matches);
+            }
+            // distances only grow with depth, so go deeper only while
+            // a closer match is still possible
+            let min = *next.iter().min().unwrap();
+            if min <= max_errors && min < d {
+                self.search_levenshtein(child, depth, query, &next, max_errors, matches);
+            }
+        }
+    }
+
+    fn report(
+        &self,
+        range: Range<usize>,
+        len: usize,
+        distance: usize,
+        matches: &mut Vec<ApproxMatch>,
+    ) {
+        matches.extend(self.suffix_array()[range].iter().map(|&p| ApproxMatch {
+            position: p,
+            len: len as u32,
+            distance: distance as u32,
+        }));
+    }
+}
+
+impl<'a, 'b> MultiDocIndex<'a, 'b> {
+    /// Returns `(doc_id, match)` with match positions relative to documents.
+    pub fn approx_doc_positions(
+        &self,
+        query: &str,
+        max_errors: usize,
+        distance: Distance,
+    ) -> Vec<(u32, ApproxMatch)> {
+        self.index()
+            .approx_positions(query, max_errors, distance)
+            .into_iter()
+            .filter_map(|m| {
+                self.locate(m.position, m.len).map(|(doc_id, pos_in_doc)| {
+                    let m = ApproxMatch {
+                        position: pos_in_doc,
+                        ..m
+                    };
+                    (doc_id, m)
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ApproxMatch, Distance};
+    use crate::test_util::to_small_alphabet;
+    use crate::IndexBuilder;
+    use quickcheck::TestResult;
+
+    fn levenshtein(a: &[char], b: &[char]) -> usize {
+        let mut row = (0..=a.len()).collect::<Vec<_>>();
+        for (i, y) in b.iter().enumerate() {
+            let mut next = vec![i + 1];
+            for (j, x) in a.iter().enumerate() {
+                next.push(
+                    (row[j] + (x != y) as usize)
+                        .min(row[j + 1] + 1)
+                        .min(next[j] + 1),
+                );
+            }
+            row = next;
+        }
+        row[a.len()]
+    }
+
+    fn hamming(a: &[char], b: &[char]) -> usize {
+        if a.len() != b.len() {
+            return usize::MAX;
+        }
+        a.iter().zip(b).filter(|(x, y)| x != y).count()
+    }
+
+    fn approx_positions_naive(
+        text: &str,
+        query: &str,
+        max_errors: usize,
+        distance: Distance,
+    ) -> Vec<ApproxMatch> {
+        let query = query.chars().collect::<Vec<_>>();
+        let boundaries = text
+            .char_indices()
+            .map(|(i, _)| i)
+            .chain(std::iter::once(text.len()))
+            .collect::<Vec<_>>();
+
+        let mut matches = Vec::new();
+        for (i, &begin) in boundaries.iter().enumerate() {
+            let best = boundaries[i + 1..]
+                .iter()
+                .map(|&end| {
+                    let s = text[begin..end].chars().collect::<Vec<_>>();
+                    let d = match distance {
+                        Distance::Hamming => hamming(&query, &s),
+                        Distance::Levenshtein => levenshtein(&query, &s),
+                    };
+                    (d, end - begin)
+                })
+                .min();
+            if let Some((d, len)) = best {
+                if d <= max_errors {
+                    matches.push(ApproxMatch {
+                        position: begin as u32,
+                        len: len as u32,
+                        distance: d as u32,
+                    });
+                }
+            }
+        }
+        matches
+    }
+
+    #[quickcheck]
+    fn approx_positions(text: String, query: String, max_errors: usize) -> TestResult {
+        if text.chars().count() > 30 || query.is_empty() || query.chars().count() > 5 {
+            return TestResult::discard();
+        }
+        let text = to_small_alphabet(&text, "abc_");
+        let query = to_small_alphabet(&query, "abc_");
+        let max_errors = max_errors % 3;
+
+        let index = IndexBuilder::new(&text).build().unwrap();
+        for &distance in &[Distance::Hamming, Distance::Levenshtein] {
+            assert_eq!(
+                index.approx_positions(&query, max_errors, distance),
+                approx_positions_naive(&text, &query, max_errors, distance)
+            );
+        }
+
+        TestResult::passed()
+    }
+
+    #[test]
+    fn typos() {
+        let text = "recieve receive reciever";
+        let index = IndexBuilder::new(text).build().unwrap();
+
+        let hamming = index.approx_positions("receive", 2, Distance::Hamming);
+        let positions = hamming.iter().map(|m| m.position).collect::<Vec<_>>();
+        assert_eq!(positions, [0, 8, 16]);
+
+        let levenshtein = index.approx_positions("receiver", 1, Distance::Levenshtein);
+        assert_eq!(
+            levenshtein,
+            [ApproxMatch {
+                position: 8,
+                len: 7,
+                distance: 1
+            }]
+        );
+    }
+}
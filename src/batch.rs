@@ -0,0 +1,135 @@
+use crate::index::DocPositions;
+use crate::{Index, MultiDocIndex};
+use std::ops::Range;
+use std::thread;
+
+impl<'a, 'b> Index<'a, 'b> {
+    /// Same as calling `positions` for each query, with results in the
+    /// order of `queries`.
+    ///
+    /// Queries are sorted so that each lookup only searches the part of the
+    /// suffix array after the previous one, and are split across threads.
+    pub fn positions_batch(&self, queries: &[&str]) -> Vec<&[u32]> {
+        self.ranges_batch(queries)
+            .into_iter()
+            .map(|range| &self.suffix_array()[range])
+            .collect()
+    }
+
+    pub fn freq_batch(&self, queries: &[&str]) -> Vec<usize> {
+        self.ranges_batch(queries)
+            .into_iter()
+            .map(|range| range.len())
+            .collect()
+    }
+
+    fn ranges_batch(&self, queries: &[&str]) -> Vec<Range<usize>> {
+        let mut order = (0..queries.len()).collect::<Vec<_>>();
+        order.sort_unstable_by_key(|&i| queries[i]);
+
+        let num_threads = thread::available_parallelism().map_or(1, |n| n.get());
+        let chunk_size = order.len().div_ceil(num_threads).max(1);
+
+        let mut ranges = vec![0..0; queries.len()];
+        thread::scope(|s| {
+            let handles = order
+                .chunks(chunk_size)
+                .map(|chunk| {
+                    s.spawn(move || {
+                        let mut from = 0;
+                        chunk
+                            .iter()
+                            .map(|&i| {
+                                let range = self.range_from(queries[i], from);
+                                from = range.start;
+                                (i, range)
+                            })
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect::<Vec<_>>();
+            for handle in handles {
+                for (i, range) in handle.join().unwrap() {
+                    ranges[i] = range;
+                }
+            }
+        });
+
+        ranges
+    }
+}
+
+impl<'a, 'b> MultiDocIndex<'a, 'b> {
+    pub fn freq_batch(&self, queries: &[&str]) -> Vec<usize> {
+        self.index()
+            .positions_batch(queries)
+            .into_iter()
+            .zip(queries)
+            .map(|(positions, query)| self.freq_of(query, positions))
+            .collect()
+    }
+
+    pub fn doc_positions_batch(&self, queries: &[&str]) -> Vec<DocPositions<'_, '_>> {
+        self.index()
+            .positions_batch(queries)
+            .into_iter()
+            .zip(queries)
+            .map(|(positions, query)| self.doc_positions_of(query, positions))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{IndexBuilder, MultiDocIndexBuilder};
+    use itertools::Itertools;
+
+    #[quickcheck]
+    fn positions_batch(text: String, queries: Vec<String>) {
+        let index = IndexBuilder::new(&text).build().unwrap();
+
+        let mut queries = queries.iter().map(String::as_str).collect::<Vec<_>>();
+        queries.extend(text.char_indices().map(|(i, _)| &text[i..]));
+        queries.extend(text.char_indices().map(|(i, _)| &text[..i]));
+
+        let expected = queries
+            .iter()
+            .map(|q| index.positions(q))
+            .collect::<Vec<_>>();
+        assert_eq!(index.positions_batch(&queries), expected);
+
+        let expected = queries.iter().map(|q| index.freq(q)).collect::<Vec<_>>();
+        assert_eq!(index.freq_batch(&queries), expected);
+    }
+
+    #[quickcheck]
+    fn doc_positions_batch(texts: Vec<String>, delim: char) {
+        let text = texts.iter().join(&delim.to_string());
+        let multi_doc_index = MultiDocIndexBuilder::new(&text)
+            .delimiter(delim)
+            .build()
+            .unwrap();
+
+        let queries = text
+            .char_indices()
+            .flat_map(|(i, _)| vec![&text[i..], &text[..i]])
+            .collect::<Vec<_>>();
+
+        let expected = queries
+            .iter()
+            .map(|q| multi_doc_index.doc_positions(q).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        let actual = multi_doc_index
+            .doc_positions_batch(&queries)
+            .into_iter()
+            .map(Iterator::collect::<Vec<_>>)
+            .collect::<Vec<_>>();
+        assert_eq!(actual, expected);
+
+        let expected = queries
+            .iter()
+            .map(|q| multi_doc_index.freq(q))
+            .collect::<Vec<_>>();
+        assert_eq!(multi_doc_index.freq_batch(&queries), expected);
+    }
+}
@@ -1,11 +1,15 @@
 use ansi_term::{Color, Style};
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use clap::{clap_app, value_t, ArgMatches};
 use memmap::Mmap;
+use std::cmp::Reverse;
 use std::fs::File;
-use std::io::{BufWriter, Write};
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
 use std::path::{Path, PathBuf};
-use suffine::{MultiDocIndex, MultiDocIndexBuilder};
+use suffine::{
+    CommonSubstring, DirDocsBuilder, Distance, DocMeta, MultiDocIndex, MultiDocIndexBuilder, Query,
+    SuffixStarts,
+};
 
 fn get_filenames(matches: &ArgMatches) -> Result<(PathBuf, PathBuf)> {
     let text_filename = value_t!(matches, "FILE", PathBuf)?;
@@ -25,32 +29,180 @@ fn open_and_map<P: AsRef<Path>>(path: P) -> Result<Mmap> {
 }
 
 fn index(matches: &ArgMatches) -> Result<()> {
-    let (text_filename, index_filename) = get_filenames(&matches)?;
+    let (text_filename, index_filename) = get_filenames(matches)?;
     let block_size = value_t!(matches, "block", u32)
         .map(|x| x * 1024 * 1024)
         .unwrap_or(u32::MAX);
-    let delimiter = value_t!(matches, "delimiter", char).unwrap_or('\n');
+    let delimiter = matches
+        .value_of("delimiter")
+        .map_or_else(|| "\n".to_string(), unescape);
+    let suffix_starts = match matches.value_of("starts") {
+        Some("words") => SuffixStarts::WordStarts,
+        Some("whitespace") => SuffixStarts::AfterWhitespace,
+        Some("lines") => SuffixStarts::LineStarts,
+        _ => SuffixStarts::All,
+    };
+
+    let write_index = |builder: &mut MultiDocIndexBuilder| -> Result<()> {
+        builder
+            .block_size(block_size)
+            .suffix_starts(suffix_starts)
+            .generalized(matches.is_present("generalized") || matches.is_present("dir"))
+            .doc_listing(matches.is_present("listing"))
+            .inverse_suffix_array(matches.is_present("inverse"));
+
+        let m_index_file = File::create(&index_filename)?;
+        let mut m_index_writer = BufWriter::new(m_index_file);
+        builder.build_to_writer_native_endian(&mut m_index_writer)?;
+        m_index_writer.flush()?;
+        Ok(())
+    };
+
+    if let Some(dir) = matches.value_of("dir") {
+        // files under the directory are concatenated into FILE
+        if text_filename.exists() && !matches.is_present("force") {
+            return Err(anyhow!(
+                "{} already exists, pass --force to overwrite it",
+                text_filename.display()
+            ));
+        }
+        let mut dir_builder = DirDocsBuilder::new(dir);
+        dir_builder
+            .skip_file(&text_filename)
+            .skip_file(&index_filename);
+        for glob in matches.values_of("include").into_iter().flatten() {
+            dir_builder.include(glob);
+        }
+        for glob in matches.values_of("exclude").into_iter().flatten() {
+            dir_builder.exclude(glob);
+        }
+        let docs = dir_builder.build()?;
+        if docs.offsets.is_empty() {
+            return Err(anyhow!("no files to index"));
+        }
+        std::fs::write(&text_filename, &docs.text)?;
+
+        return write_index(
+            MultiDocIndexBuilder::new(&docs.text)
+                .doc_offsets(docs.offsets)
+                .doc_meta(docs.metas),
+        );
+    }
 
     let text_mmap = open_and_map(&text_filename)?;
     let text = unsafe { std::str::from_utf8_unchecked(&text_mmap) };
 
-    let m_index_file = File::create(index_filename)?;
-    let mut m_index_writer = BufWriter::new(m_index_file);
+    let mut builder = MultiDocIndexBuilder::new(text);
+    builder.delimiter(delimiter);
+    if let Some(meta_filename) = matches.value_of("meta") {
+        builder.doc_meta(read_meta(meta_filename)?);
+    }
+    write_index(&mut builder)
+}
 
-    MultiDocIndexBuilder::new(text)
-        .block_size(block_size)
-        .delimiter(delimiter)
-        .build_to_writer_native_endian(&mut m_index_writer)?;
+// Reads metadata of one document per line in the form of
+// `name<TAB>id<TAB>key=value<TAB>...`, where empty name and id are omitted.
+fn read_meta(filename: &str) -> Result<Vec<DocMeta>> {
+    let mut metas = Vec::new();
+    for line in BufReader::new(File::open(filename)?).lines() {
+        let line = line?;
+        let mut columns = line.split('\t');
+        let mut next_column = || columns.next().filter(|s| !s.is_empty()).map(str::to_string);
+        let name = next_column();
+        let id = next_column();
+        let fields = columns
+            .map(|field| {
+                let (key, value) = field.split_once('=').unwrap_or((field, ""));
+                (key.to_string(), value.to_string())
+            })
+            .collect();
+        metas.push(DocMeta { name, id, fields });
+    }
+    Ok(metas)
+}
 
-    m_index_writer.flush()?;
+// Interprets \n, \r, \t and \\ in a command-line argument.
+fn unescape(s: &str) -> String {
+    let mut unescaped = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => unescaped.push('\n'),
+            Some('r') => unescaped.push('\r'),
+            Some('t') => unescaped.push('\t'),
+            Some(c) => unescaped.push(c),
+            None => unescaped.push('\\'),
+        }
+    }
+    unescaped
+}
 
-    Ok(())
+fn read_queries(matches: &ArgMatches) -> Result<Vec<String>> {
+    let filename = value_t!(matches, "queries", String)?;
+    let queries = if filename == "-" {
+        io::stdin().lock().lines().collect::<io::Result<_>>()?
+    } else {
+        BufReader::new(File::open(filename)?)
+            .lines()
+            .collect::<io::Result<_>>()?
+    };
+    Ok(queries)
+}
+
+// Returns the name or ID of the document from its metadata, falling back to
+// its position in the index.
+fn doc_name(multi_doc_index: &MultiDocIndex, doc_id: u32) -> String {
+    multi_doc_index
+        .doc_meta(doc_id)
+        .and_then(|meta| meta.name.or(meta.id))
+        .unwrap_or_else(|| doc_id.to_string())
+}
+
+fn print_hit(multi_doc_index: &MultiDocIndex, highlighted: Style, doc_id: u32, pos: u32, len: u32) {
+    if let Some(doc_text) = multi_doc_index.doc(doc_id) {
+        let (pos, len) = (pos as usize, len as usize);
+        let source = multi_doc_index
+            .doc_meta(doc_id)
+            .and_then(|meta| meta.name.or(meta.id));
+        match source {
+            // prints the lines containing the hit like grep
+            Some(source) => {
+                let begin = doc_text[..pos].rfind('\n').map_or(0, |i| i + 1);
+                let end = doc_text[pos + len..]
+                    .find('\n')
+                    .map_or(doc_text.len(), |i| pos + len + i);
+                println!(
+                    "{}:{}:{}{}{}",
+                    source,
+                    doc_text[..pos].matches('\n').count() + 1,
+                    &doc_text[begin..pos],
+                    highlighted.paint(&doc_text[pos..pos + len]),
+                    &doc_text[pos + len..end],
+                );
+            }
+            None => println!(
+                "{}{}{}",
+                &doc_text[..pos],
+                highlighted.paint(&doc_text[pos..pos + len]),
+                &doc_text[pos + len..],
+            ),
+        }
+    }
 }
 
 fn search(matches: &ArgMatches) -> Result<()> {
     let (text_filename, index_filename) = get_filenames(matches)?;
-    let query = value_t!(matches, "QUERY", String)?;
     let nhits = value_t!(matches, "nhits", usize).unwrap_or(usize::MAX);
+    let max_errors = value_t!(matches, "maxerrors", usize).ok();
+    let distance = if matches.is_present("hamming") {
+        Distance::Hamming
+    } else {
+        Distance::Levenshtein
+    };
 
     let text_mmap = open_and_map(&text_filename)?;
     let text = unsafe { std::str::from_utf8_unchecked(&text_mmap) };
@@ -58,32 +210,318 @@ fn search(matches: &ArgMatches) -> Result<()> {
     let m_index_mmap = open_and_map(index_filename)?;
     let multi_doc_index = MultiDocIndex::from_bytes(text, &m_index_mmap)?;
 
+    let highlighted = if matches.is_present("nocolor") {
+        Style::new()
+    } else {
+        Style::new().bold().fg(Color::Green)
+    };
+
+    if matches.is_present("queries") {
+        let queries = read_queries(matches)?;
+        let queries = queries.iter().map(String::as_str).collect::<Vec<_>>();
+        if matches.is_present("count") {
+            for (query, count) in queries.iter().zip(multi_doc_index.freq_batch(&queries)) {
+                println!("{}\t{}", query, count);
+            }
+        } else {
+            let results = multi_doc_index.doc_positions_batch(&queries);
+            for (query, doc_positions) in queries.iter().zip(results) {
+                for (doc_id, pos) in doc_positions.take(nhits) {
+                    print_hit(
+                        &multi_doc_index,
+                        highlighted,
+                        doc_id,
+                        pos,
+                        query.len() as u32,
+                    );
+                }
+            }
+        }
+        return Ok(());
+    }
+
+    let query = value_t!(matches, "QUERY", String)?;
+
+    if matches.is_present("top") {
+        let k = value_t!(matches, "top", usize)?;
+        for (doc_id, count) in multi_doc_index.top_docs(&query, k) {
+            println!("{}\t{}", count, doc_name(&multi_doc_index, doc_id));
+        }
+        return Ok(());
+    }
+
+    if matches.is_present("rank") {
+        let terms = query.split_whitespace().collect::<Vec<_>>();
+        for (doc_id, score) in multi_doc_index.rank(&terms, nhits) {
+            println!("{:.4}\t{}", score, doc_name(&multi_doc_index, doc_id));
+        }
+        return Ok(());
+    }
+
+    if matches.is_present("docs") || matches.is_present("querylang") {
+        let doc_ids = if matches.is_present("querylang") {
+            multi_doc_index.docs_matching(&Query::parse(&query)?)
+        } else {
+            multi_doc_index.docs_containing(&query)
+        };
+        if matches.is_present("count") {
+            println!("{}", doc_ids.len());
+            return Ok(());
+        }
+        for doc_id in doc_ids.into_iter().take(nhits) {
+            println!("{}", doc_name(&multi_doc_index, doc_id));
+        }
+        return Ok(());
+    }
+
     if matches.is_present("count") {
-        println!("{}", multi_doc_index.freq(&query));
+        let count = if matches.is_present("regex") {
+            multi_doc_index.regex_doc_positions(&query)?.len()
+        } else if let Some(max_errors) = max_errors {
+            multi_doc_index
+                .approx_doc_positions(&query, max_errors, distance)
+                .len()
+        } else {
+            multi_doc_index.freq(&query)
+        };
+        println!("{}", count);
         return Ok(());
     }
 
-    let highlighted = if matches.is_present("nocolor") {
-        Style::new()
+    let print_hit = |doc_id, pos, len| print_hit(&multi_doc_index, highlighted, doc_id, pos, len);
+
+    if matches.is_present("regex") {
+        for (doc_id, pos, len) in multi_doc_index
+            .regex_doc_positions(&query)?
+            .into_iter()
+            .take(nhits)
+        {
+            print_hit(doc_id, pos, len);
+        }
+    } else if let Some(max_errors) = max_errors {
+        for (doc_id, m) in multi_doc_index
+            .approx_doc_positions(&query, max_errors, distance)
+            .into_iter()
+            .take(nhits)
+        {
+            print_hit(doc_id, m.position, m.len);
+        }
     } else {
-        Style::new().bold().fg(Color::Green)
+        for (doc_id, pos) in multi_doc_index.doc_positions(&query).take(nhits) {
+            print_hit(doc_id, pos, query.len() as u32);
+        }
+    }
+
+    Ok(())
+}
+
+fn repeats(matches: &ArgMatches) -> Result<()> {
+    let (text_filename, index_filename) = get_filenames(matches)?;
+    let min_len = value_t!(matches, "minlen", usize).unwrap_or(1);
+    let min_freq = value_t!(matches, "minfreq", usize).unwrap_or(2);
+    let nrepeats = value_t!(matches, "nrepeats", usize).unwrap_or(usize::MAX);
+
+    let text_mmap = open_and_map(&text_filename)?;
+    let text = unsafe { std::str::from_utf8_unchecked(&text_mmap) };
+
+    let m_index_mmap = open_and_map(index_filename)?;
+    let multi_doc_index = MultiDocIndex::from_bytes(text, &m_index_mmap)?;
+    let index = multi_doc_index.index();
+
+    let mut repeats = if matches.is_present("longest") {
+        index.longest_repeated_substring().into_iter().collect()
+    } else if matches.is_present("supermaximal") {
+        index.supermaximal_repeats(min_len, min_freq)
+    } else {
+        index.maximal_repeats(min_len, min_freq)
+    };
+    repeats.sort_by_key(|r| (Reverse(r.substring.len()), Reverse(r.positions.len())));
+
+    for repeat in repeats.iter().take(nrepeats) {
+        println!(
+            "{}\t{}",
+            repeat.positions.len(),
+            repeat.substring.escape_debug()
+        );
+    }
+
+    Ok(())
+}
+
+fn factors(matches: &ArgMatches) -> Result<()> {
+    let (text_filename, index_filename) = get_filenames(matches)?;
+
+    let text_mmap = open_and_map(&text_filename)?;
+    let text = unsafe { std::str::from_utf8_unchecked(&text_mmap) };
+
+    let m_index_mmap = open_and_map(index_filename)?;
+    let multi_doc_index = MultiDocIndex::from_bytes(text, &m_index_mmap)?;
+
+    let stdout = io::stdout();
+    let mut out = BufWriter::new(stdout.lock());
+    let index = multi_doc_index.index();
+    let factors: Box<dyn Iterator<Item = _>> = if matches.is_present("lzend") {
+        Box::new(index.lz_end_factors())
+    } else {
+        Box::new(index.lz77_factors()?)
     };
+    let (mut num_factors, mut num_literals) = (0, 0);
+    for factor in factors {
+        num_factors += 1;
+        if factor.source.is_none() {
+            num_literals += 1;
+        }
+        if matches.is_present("phrases") {
+            match factor.source {
+                Some(source) => writeln!(out, "{}\t{}\t{}", factor.position, factor.len, source)?,
+                None => writeln!(out, "{}\t{}\t-", factor.position, factor.len)?,
+            }
+        }
+    }
+    if !matches.is_present("phrases") {
+        writeln!(out, "factors:\t{}", num_factors)?;
+        writeln!(out, "literals:\t{}", num_literals)?;
+        if num_factors > 0 {
+            writeln!(
+                out,
+                "average length:\t{:.2} bytes",
+                text.len() as f64 / num_factors as f64
+            )?;
+        }
+    }
+    out.flush()?;
+
+    Ok(())
+}
+
+fn kmers(matches: &ArgMatches) -> Result<()> {
+    let (text_filename, index_filename) = get_filenames(matches)?;
+    let k = value_t!(matches, "k", usize)?;
+    let min_count = value_t!(matches, "mincount", usize).unwrap_or(1);
+
+    let text_mmap = open_and_map(&text_filename)?;
+    let text = unsafe { std::str::from_utf8_unchecked(&text_mmap) };
+
+    let m_index_mmap = open_and_map(index_filename)?;
+    let multi_doc_index = MultiDocIndex::from_bytes(text, &m_index_mmap)?;
+
+    let stdout = io::stdout();
+    let mut out = BufWriter::new(stdout.lock());
+    if let Ok(n) = value_t!(matches, "nkmers", usize) {
+        for (kmer, positions) in multi_doc_index.top_kmers(k, n) {
+            if positions.len() < min_count {
+                break;
+            }
+            writeln!(out, "{}\t{}", positions.len(), kmer.escape_debug())?;
+        }
+    } else {
+        // streamed in lexicographic order as they are found
+        for (kmer, positions) in multi_doc_index.kmers(k, min_count) {
+            writeln!(out, "{}\t{}", positions.len(), kmer.escape_debug())?;
+        }
+    }
+    out.flush()?;
+
+    Ok(())
+}
+
+fn common(matches: &ArgMatches) -> Result<()> {
+    let (text_filename, index_filename) = get_filenames(matches)?;
+    let min_len = value_t!(matches, "minlen", usize).ok();
+
+    let text_mmap = open_and_map(&text_filename)?;
+    let text = unsafe { std::str::from_utf8_unchecked(&text_mmap) };
+
+    let m_index_mmap = open_and_map(index_filename)?;
+    let multi_doc_index = MultiDocIndex::from_bytes(text, &m_index_mmap)?;
 
-    for (doc_id, pos) in multi_doc_index.doc_positions(&query).take(nhits) {
-        if let Some(doc_text) = multi_doc_index.doc(doc_id) {
-            let pos = pos as usize;
+    let print = |a: &str, substrings: Vec<CommonSubstring>| {
+        for c in substrings {
             println!(
-                "{}{}{}",
-                &doc_text[..pos],
-                highlighted.paint(&query),
-                &doc_text[pos + query.len()..],
+                "{}\t{}\t{}\t{}",
+                c.position_a,
+                c.position_b,
+                c.len,
+                a[c.position_a as usize..(c.position_a + c.len) as usize].escape_debug()
             );
         }
+    };
+
+    if matches.is_present("OTHER") {
+        let other_filename = value_t!(matches, "OTHER", PathBuf)?;
+        let other_index_filename = value_t!(matches, "otherindex", PathBuf).unwrap_or_else(|_| {
+            let mut other_index_filename = other_filename.clone();
+            other_index_filename.set_extension("suffine-index");
+            other_index_filename
+        });
+
+        let other_mmap = open_and_map(&other_filename)?;
+        let other = unsafe { std::str::from_utf8_unchecked(&other_mmap) };
+
+        let other_m_index_mmap = open_and_map(other_index_filename)?;
+        let other_multi_doc_index = MultiDocIndex::from_bytes(other, &other_m_index_mmap)?;
+
+        let (index, other_index) = (multi_doc_index.index(), other_multi_doc_index.index());
+        let substrings = match min_len {
+            Some(min_len) => index.common_substrings(other_index, min_len)?,
+            None => index.longest_common_substrings(other_index)?,
+        };
+        print(text, substrings);
+    } else {
+        let doc_a = value_t!(matches, "doca", u32)?;
+        let doc_b = value_t!(matches, "docb", u32)?;
+        let substrings = match min_len {
+            Some(min_len) => multi_doc_index.common_substrings(doc_a, doc_b, min_len)?,
+            None => multi_doc_index.longest_common_substrings(doc_a, doc_b)?,
+        }
+        .ok_or_else(|| anyhow!("document does not exist"))?;
+        print(multi_doc_index.doc(doc_a).unwrap(), substrings);
     }
 
     Ok(())
 }
 
+fn stats(matches: &ArgMatches) -> Result<()> {
+    let (text_filename, index_filename) = get_filenames(matches)?;
+
+    let text_mmap = open_and_map(&text_filename)?;
+    let text = unsafe { std::str::from_utf8_unchecked(&text_mmap) };
+
+    let m_index_mmap = open_and_map(index_filename)?;
+    let multi_doc_index = MultiDocIndex::from_bytes(text, &m_index_mmap)?;
+    let (stats, doc_stats) = multi_doc_index.stats();
+
+    println!("text length:\t{} bytes", stats.text_len);
+    println!("characters:\t{}", stats.char_count);
+    println!(
+        "alphabet:\t{} ({})",
+        stats.alphabet.len(),
+        stats.alphabet.iter().collect::<String>().escape_debug()
+    );
+    println!("distinct substrings:\t{}", stats.distinct_substrings);
+    println!("max LCP:\t{}", stats.max_lcp);
+    println!("average LCP:\t{:.2}", stats.avg_lcp);
+    println!("documents:\t{}", doc_stats.num_docs);
+    let [min, q1, median, q3, max] = doc_stats.doc_len_quartiles;
+    println!(
+        "document length:\tmin {} / q1 {} / median {} / q3 {} / max {} / average {:.2}",
+        min, q1, median, q3, max, doc_stats.avg_doc_len
+    );
+    println!(
+        "index size:\t{} bytes (suffix array {}, inverse suffix array {}, offsets {}, delimiter {}, metadata {}, doc listing {}, footer {})",
+        m_index_mmap.len(),
+        stats.suffix_array_size,
+        stats.inverse_suffix_array_size,
+        doc_stats.offsets_size,
+        doc_stats.delimiter_size,
+        doc_stats.meta_size,
+        doc_stats.listing_size,
+        doc_stats.footer_size
+    );
+
+    Ok(())
+}
+
 fn main() -> Result<()> {
     let matches = clap_app!(suffine =>
         (version: env!("CARGO_PKG_VERSION"))
@@ -91,19 +529,71 @@ fn main() -> Result<()> {
         (about: env!("CARGO_PKG_DESCRIPTION"))
         (@setting SubcommandRequiredElseHelp)
         (@subcommand index =>
-            (@arg FILE: * "File containing the text to index")
+            (@arg FILE: * "File containing the text to index, or to write the text of files under <dir> to")
             (@arg index: -i --index +takes_value "Suffine index filepath")
             (@arg block: -b --block +takes_value "Block size in MB. By default index is built in single large block")
-            (@arg delimiter: -d --delimiter +takes_value "String used to separate items. Defaults to newline character")
+            (@arg dir: --dir +takes_value conflicts_with[delimiter meta] "Indexes files under the directory as documents named after their paths")
+            (@arg force: -f --force requires("dir") "Overwrites FILE with the text of files under <dir> if it exists")
+            (@arg include: --include +takes_value +multiple number_of_values(1) requires("dir") "Indexes only files whose paths relative to <dir> match the glob")
+            (@arg exclude: --exclude +takes_value +multiple number_of_values(1) requires("dir") "Skips files and directories whose paths relative to <dir> match the glob")
+            (@arg delimiter: -d --delimiter +takes_value "String used to separate items, where \\n, \\r and \\t are interpreted. Defaults to newline character")
+            (@arg starts: -s --starts +takes_value possible_values(&["all", "words", "whitespace", "lines"]) "Indexes only suffixes starting at these positions. Defaults to all")
+            (@arg generalized: -g --generalized "Sorts suffixes as if they ended at the ends of documents, so that repeats stay within documents. Implied by --dir")
+            (@arg listing: -l --listing "Stores structures to list documents for --docs, --query-lang and --top without enumerating hits")
+            (@arg inverse: --inverse "Stores the inverse suffix array, which is otherwise computed by repeats, kmers, stats and common")
+            (@arg meta: -m --meta +takes_value "File containing tab-separated name, ID and key=value fields of each document per line")
         )
         (@subcommand search =>
             (@arg FILE: * "File containing the text")
-            (@arg QUERY: * -q --query +takes_value "Query string")
+            (@arg QUERY: -q --query +takes_value required_unless("queries") "Query string")
+            (@arg queries: --queries +takes_value conflicts_with[QUERY regex maxerrors] "File containing one query per line, or - for standard input")
             (@arg index: -i --index +takes_value "Suffine index filepath")
             (@arg delimiter: -d --delimiter +takes_value "Character used to separate items. Defaults to newline character")
             (@arg nhits: -n +takes_value "Outputs first <nhits> hits")
+            (@arg regex: -r --regex "Interprets QUERY as a regular expression")
+            (@arg maxerrors: -e --("max-errors") +takes_value conflicts_with("regex") "Allows matches within <maxerrors> edit distance of QUERY")
+            (@arg hamming: --hamming requires("maxerrors") "Counts only substitutions as errors")
             (@arg nocolor: --("no-color") "Prints all output without color")
             (@arg count: -c --count conflicts_with("nhits") "Counts hits without listing")
+            (@arg docs: --docs conflicts_with[queries regex maxerrors] "Outputs each document containing QUERY once")
+            (@arg querylang: --("query-lang") conflicts_with[queries regex maxerrors docs] "Interprets QUERY as a boolean query and outputs matching documents")
+            (@arg rank: --rank conflicts_with[queries regex maxerrors docs querylang count] "Outputs documents containing any of whitespace-separated terms in QUERY in descending order of BM25 scores")
+            (@arg top: --top +takes_value conflicts_with[queries regex maxerrors docs querylang rank count nhits] "Outputs the <top> documents containing QUERY most often along with the numbers of hits")
+        )
+        (@subcommand repeats =>
+            (@arg FILE: * "File containing the text")
+            (@arg index: -i --index +takes_value "Suffine index filepath")
+            (@arg minlen: -l --("min-len") +takes_value "Outputs repeats of at least <minlen> bytes. Defaults to 1")
+            (@arg minfreq: -f --("min-freq") +takes_value "Outputs repeats occurring at least <minfreq> times. Defaults to 2")
+            (@arg nrepeats: -n +takes_value "Outputs first <nrepeats> repeats")
+            (@arg supermaximal: -s --supermaximal "Outputs only repeats not contained in other repeats")
+            (@arg longest: --longest conflicts_with[minlen minfreq supermaximal] "Outputs the longest repeated substring")
+        )
+        (@subcommand stats =>
+            (@arg FILE: * "File containing the text")
+            (@arg index: -i --index +takes_value "Suffine index filepath")
+        )
+        (@subcommand factors =>
+            (@arg FILE: * "File containing the text")
+            (@arg index: -i --index +takes_value "Suffine index filepath")
+            (@arg phrases: -p --phrases "Outputs position, length and source of every phrase instead of counts")
+            (@arg lzend: --("lz-end") "Computes the LZ-End factorization, whose phrases end where earlier phrases end")
+        )
+        (@subcommand kmers =>
+            (@arg FILE: * "File containing the text")
+            (@arg index: -i --index +takes_value "Suffine index filepath")
+            (@arg k: -k +takes_value +required "Length of substrings in characters")
+            (@arg mincount: -m --("min-count") +takes_value "Outputs substrings occurring at least <mincount> times")
+            (@arg nkmers: -n +takes_value "Outputs the <nkmers> most frequent substrings")
+        )
+        (@subcommand common =>
+            (@arg FILE: * "File containing the text")
+            (@arg OTHER: required_unless("doca") "Another indexed file to compare with")
+            (@arg index: -i --index +takes_value "Suffine index filepath")
+            (@arg otherindex: -I --("other-index") +takes_value requires("OTHER") "Suffine index filepath of OTHER")
+            (@arg doca: -a +takes_value requires("docb") "Compares document <doca> of FILE with document <docb>")
+            (@arg docb: -b +takes_value requires("doca") "Compares document <docb> of FILE with document <doca>")
+            (@arg minlen: -l --("min-len") +takes_value "Outputs all maximal common substrings of at least <minlen> bytes instead of the longest ones")
         )
     )
     .get_matches();
@@ -111,6 +601,11 @@ fn main() -> Result<()> {
     match matches.subcommand() {
         ("index", Some(m)) => index(m)?,
         ("search", Some(m)) => search(m)?,
+        ("repeats", Some(m)) => repeats(m)?,
+        ("stats", Some(m)) => stats(m)?,
+        ("factors", Some(m)) => factors(m)?,
+        ("kmers", Some(m)) => kmers(m)?,
+        ("common", Some(m)) => common(m)?,
         _ => unreachable!(),
     };
 
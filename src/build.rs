@@ -1,9 +1,11 @@
-use crate::Result;
+use crate::index::Docs;
+use crate::{Result, SuffixStarts};
 use byteorder::{ByteOrder, NativeEndian, ReadBytesExt, WriteBytesExt};
 use std::cmp::{Ordering, Reverse};
 use std::collections::BinaryHeap;
 use std::fs::File;
 use std::io::{self, BufReader, BufWriter, Write};
+use std::ops::Range;
 use suffix::SuffixTable;
 use tempfile::NamedTempFile;
 
@@ -53,6 +55,349 @@ where
     }
 }
 
+struct Filter<'s, B> {
+    text: &'s str,
+    starts: &'s SuffixStarts,
+    buffer: B,
+    num_written: usize,
+}
+
+impl<B, O> IntBuffer<u32, O> for &mut Filter<'_, B>
+where
+    B: IntBuffer<u32, O>,
+    O: ByteOrder,
+{
+    fn write(&mut self, n: u32) -> Result<()> {
+        if self.starts.accepts(self.text, n as usize) {
+            self.buffer.write(n)?;
+            self.num_written += 1;
+        }
+        Ok(())
+    }
+}
+
+// Same as build_suffix_array, including only the suffixes selected by
+// `starts`.
+pub fn build_sparse_suffix_array<B, O>(
+    text: &str,
+    block_size: u32,
+    starts: &SuffixStarts,
+    buffer: B,
+) -> Result<usize>
+where
+    B: IntBuffer<u32, O>,
+    O: ByteOrder,
+{
+    if starts.is_all() {
+        return build_suffix_array(text, block_size, buffer);
+    }
+    let mut filter = Filter {
+        text,
+        starts,
+        buffer,
+        num_written: 0,
+    };
+    build_suffix_array(text, block_size, &mut filter)?;
+    Ok(filter.num_written)
+}
+
+// Same as build_sparse_suffix_array, ordering the suffixes as if each of them
+// ended at the end of its document with a terminator unique to the document,
+// and leaving out the suffixes starting in delimiters. Blocks consist of whole
+// documents, so documents longer than `block_size` make larger blocks.
+pub fn build_generalized_suffix_array<B, O>(
+    text: &str,
+    docs: &Docs,
+    block_size: u32,
+    starts: &SuffixStarts,
+    mut buffer: B,
+) -> Result<usize>
+where
+    B: IntBuffer<u32, O>,
+    O: ByteOrder,
+{
+    if text.len() <= block_size as usize {
+        let mut num_written = 0;
+        for x in sort_doc_suffixes(text, docs, 0..text.len(), starts) {
+            buffer.write(x)?;
+            num_written += 1;
+        }
+        return Ok(num_written);
+    }
+
+    let mut heap = BinaryHeap::new();
+    let mut begin = 0;
+    while begin < text.len() {
+        // extended to the start of the next document
+        let next_doc = docs
+            .offsets
+            .partition_point(|&offset| (offset as usize) < begin + block_size as usize);
+        let end = docs
+            .offsets
+            .get(next_doc)
+            .map_or(text.len(), |&offset| offset as usize);
+
+        let file = NamedTempFile::new()?;
+        {
+            let mut writer = BufWriter::new(&file);
+            for x in sort_doc_suffixes(text, docs, begin..end, starts) {
+                writer.write_u32::<NativeEndian>(x - begin as u32)?;
+            }
+            writer.flush()?;
+        }
+
+        let block = Block {
+            text: &text[begin..],
+            docs: Some(docs),
+            reader: BufReader::new(file.reopen()?),
+            begin,
+            front_index: 0,
+        };
+        if let Some(block) = block.next() {
+            heap.push(Reverse(block));
+        }
+
+        begin = end;
+    }
+
+    merge_blocks(heap, buffer)
+}
+
+// Sorts the suffixes starting in `range`, which consists of whole documents,
+// in the order of build_generalized_suffix_array.
+fn sort_doc_suffixes(
+    text: &str,
+    docs: &Docs,
+    range: Range<usize>,
+    starts: &SuffixStarts,
+) -> Vec<u32> {
+    if range.is_empty() {
+        return Vec::new();
+    }
+    let begin = range.start;
+    let st = SuffixTable::new(&text[range]);
+    let table = st.table();
+    let (mut sa, lcp) = filter_suffixes(
+        table.iter().map(|&x| x + begin as u32),
+        byte_lcp_array(st.text().as_bytes(), table),
+        |x| {
+            text.is_char_boundary(x as usize)
+                && docs.suffix_end(x).is_some()
+                && starts.accepts(text, x as usize)
+        },
+    );
+    sort_by_doc_suffix(&mut sa, &lcp, docs);
+    sa
+}
+
+// Keeps the suffixes accepted by `pred`, along with the longest common
+// prefixes of adjacent ones, which are the minima of `lcp` in between.
+pub(crate) fn filter_suffixes<I, F>(sa: I, lcp: Vec<u32>, pred: F) -> (Vec<u32>, Vec<u32>)
+where
+    I: IntoIterator<Item = u32>,
+    F: Fn(u32) -> bool,
+{
+    let mut filtered_sa = Vec::new();
+    let mut filtered_lcp = lcp;
+    let mut min_lcp = u32::MAX;
+    for (i, x) in sa.into_iter().enumerate() {
+        min_lcp = min_lcp.min(filtered_lcp[i]);
+        if pred(x) {
+            filtered_lcp[filtered_sa.len()] = if filtered_sa.is_empty() { 0 } else { min_lcp };
+            filtered_sa.push(x);
+            min_lcp = u32::MAX;
+        }
+    }
+    filtered_lcp.truncate(filtered_sa.len());
+    (filtered_sa, filtered_lcp)
+}
+
+// Kasai et al.'s algorithm over bytes, where `sa` contains every position.
+fn byte_lcp_array(text: &[u8], sa: &[u32]) -> Vec<u32> {
+    let rank = invert(sa, text.len());
+    let mut lcp = vec![0; sa.len()];
+    let mut h = 0;
+    for p in 0..text.len() {
+        let r = rank[p] as usize;
+        if r == 0 {
+            h = 0;
+            continue;
+        }
+        let q = sa[r - 1] as usize;
+        while p + h < text.len() && q + h < text.len() && text[p + h] == text[q + h] {
+            h += 1;
+        }
+        lcp[r] = h as u32;
+        h = h.saturating_sub(1);
+    }
+    lcp
+}
+
+// Reorders suffixes sorted as whole suffixes into the order of their document
+// suffixes, where `lcp` is that of adjacent whole suffixes with `lcp[0] == 0`.
+//
+// Whole suffixes starting with a document suffix `s` form a run, in which the
+// suffix whose document suffix is `s` comes first in the new order, after
+// those whose document suffixes are prefixes of `s`. Sorting by the start of
+// the run, the length of the document suffix and the position thus gives the
+// new order, and only reorders the runs longer than one suffix.
+pub(crate) fn sort_by_doc_suffix(sa: &mut [u32], lcp: &[u32], docs: &Docs) {
+    let doc_suffix_len = |x: u32| docs.suffix_end(x).unwrap() - x;
+
+    // The run of a suffix starts at the last index not greater than its own
+    // at which lcp is less than the length of its document suffix. The stack
+    // keeps the indices with lcp less than those of all the following ones.
+    let mut run_starts = Vec::with_capacity(sa.len());
+    let mut stack: Vec<usize> = Vec::new();
+    for (i, &x) in sa.iter().enumerate() {
+        while stack.last().is_some_and(|&k| lcp[k] >= lcp[i]) {
+            stack.pop();
+        }
+        stack.push(i);
+        let len = doc_suffix_len(x);
+        let j = stack.partition_point(|&k| lcp[k] < len);
+        run_starts.push(stack[j - 1] as u32);
+    }
+
+    // union of the ranges from the start of the run to each suffix
+    let mut segments: Vec<Range<usize>> = Vec::new();
+    for (i, &start) in run_starts.iter().enumerate() {
+        let mut start = start as usize;
+        if start == i {
+            continue;
+        }
+        while let Some(last) = segments.last() {
+            if last.end <= start {
+                break;
+            }
+            start = start.min(last.start);
+            segments.pop();
+        }
+        segments.push(start..i + 1);
+    }
+
+    for segment in segments {
+        let mut keys = segment
+            .clone()
+            .map(|i| (run_starts[i], doc_suffix_len(sa[i]), sa[i]))
+            .collect::<Vec<_>>();
+        keys.sort_unstable();
+        for (x, (_, _, p)) in sa[segment].iter_mut().zip(keys) {
+            *x = p;
+        }
+    }
+}
+
+// Writes the rank of the suffix starting at each byte position, or u32::MAX
+// if the position is not at a character boundary.
+pub fn build_inverse_suffix_array<B, O>(
+    text: &str,
+    block_size: u32,
+    starts: &SuffixStarts,
+    mut buffer: B,
+) -> Result<usize>
+where
+    B: IntBuffer<u32, O>,
+    O: ByteOrder,
+{
+    if text.len() <= block_size as usize {
+        let mut sa = VecWrapper(Vec::new());
+        build_sparse_suffix_array(text, block_size, starts, &mut sa)?;
+        for x in invert(&sa.0, text.len()) {
+            buffer.write(x)?;
+        }
+        return Ok(text.len());
+    }
+
+    let file = NamedTempFile::new()?;
+    {
+        let mut writer = BufWriter::new(&file);
+        build_sparse_suffix_array::<_, NativeEndian>(text, block_size, starts, &mut writer)?;
+        writer.flush()?;
+    }
+    invert_external(|| read_u32s(&file), text.len(), block_size, buffer)?;
+
+    Ok(text.len())
+}
+
+// Same as invert for a suffix array read by `sa`, scattering ranks to
+// temporary files, one for each block of `block_size` positions, in a single
+// pass over the suffix array.
+pub(crate) fn invert_external<S, I, B, O>(
+    mut sa: S,
+    text_len: usize,
+    block_size: u32,
+    mut buffer: B,
+) -> Result<()>
+where
+    S: FnMut() -> Result<I>,
+    I: Iterator<Item = Result<u32>>,
+    B: IntBuffer<u32, O>,
+    O: ByteOrder,
+{
+    let block_size = block_size as usize;
+    if text_len <= block_size {
+        let mut inverse = vec![u32::MAX; text_len];
+        for (rank, x) in sa()?.enumerate() {
+            inverse[x? as usize] = rank as u32;
+        }
+        for x in inverse {
+            buffer.write(x)?;
+        }
+        return Ok(());
+    }
+
+    let files = (0..text_len.div_ceil(block_size))
+        .map(|_| NamedTempFile::new())
+        .collect::<io::Result<Vec<_>>>()?;
+    {
+        let mut writers = files.iter().map(BufWriter::new).collect::<Vec<_>>();
+        for (rank, x) in sa()?.enumerate() {
+            let x = x? as usize;
+            let writer = &mut writers[x / block_size];
+            writer.write_u32::<NativeEndian>((x % block_size) as u32)?;
+            writer.write_u32::<NativeEndian>(rank as u32)?;
+        }
+        for writer in &mut writers {
+            writer.flush()?;
+        }
+    }
+
+    for (i, file) in files.iter().enumerate() {
+        let begin = i * block_size;
+        let mut block = vec![u32::MAX; (text_len - begin).min(block_size)];
+        let mut pairs = read_u32s(file)?;
+        while let Some(x) = pairs.next() {
+            block[x? as usize] = pairs.next().unwrap()?;
+        }
+        for x in block {
+            buffer.write(x)?;
+        }
+    }
+
+    Ok(())
+}
+
+// Reads u32s written to a temporary file in native endian.
+pub(crate) fn read_u32s(file: &NamedTempFile) -> Result<impl Iterator<Item = Result<u32>>> {
+    let mut reader = BufReader::new(file.reopen()?);
+    Ok(std::iter::from_fn(move || {
+        match reader.read_u32::<NativeEndian>() {
+            Ok(x) => Some(Ok(x)),
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => None,
+            Err(e) => Some(Err(e.into())),
+        }
+    }))
+}
+
+pub fn invert(suffix_array: &[u32], text_len: usize) -> Vec<u32> {
+    let mut inverse = vec![u32::MAX; text_len];
+    for (rank, &x) in suffix_array.iter().enumerate() {
+        inverse[x as usize] = rank as u32;
+    }
+    inverse
+}
+
 fn build_suffix_array_in_memory<B, O>(text: &str, len: usize, mut buffer: B) -> Result<usize>
 where
     B: IntBuffer<u32, O>,
@@ -74,6 +419,8 @@ where
 
 struct Block<'a> {
     text: &'a str,
+    // set if suffixes end at the ends of documents
+    docs: Option<&'a Docs<'a>>,
     reader: BufReader<File>,
     begin: usize,
     front_index: u32,
@@ -88,24 +435,31 @@ impl PartialEq for Block<'_> {
 }
 
 impl Ord for Block<'_> {
-    fn cmp(&self, _: &Self) -> Ordering {
-        unimplemented!()
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.front_suffix()
+            .as_bytes()
+            .cmp(other.front_suffix().as_bytes())
+            .then(self.front_position().cmp(&other.front_position()))
     }
 }
 
 impl PartialOrd for Block<'_> {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        Some(
-            self.front_suffix()
-                .as_bytes()
-                .cmp(other.front_suffix().as_bytes()),
-        )
+        Some(self.cmp(other))
     }
 }
 
 impl Block<'_> {
     fn front_suffix(&self) -> &str {
-        &self.text[self.front_index as usize..]
+        let end = match self.docs {
+            Some(docs) => docs.suffix_end(self.front_position()).unwrap() as usize - self.begin,
+            None => self.text.len(),
+        };
+        &self.text[self.front_index as usize..end]
+    }
+
+    fn front_position(&self) -> u32 {
+        self.begin as u32 + self.front_index
     }
 
     fn next(mut self) -> Option<Self> {
@@ -120,26 +474,24 @@ impl Block<'_> {
     }
 }
 
-fn calc_tail_len(text: &str, pat: &str) -> Option<usize> {
-    let mut occ_pos = 0;
-    let mut prefix_len = 1;
-    while !pat.is_char_boundary(prefix_len) {
-        prefix_len += 1;
-    }
-    while let Some(i) = &text[occ_pos..].find(&pat[..prefix_len]) {
-        if prefix_len == pat.len() {
-            return None;
-        }
-        occ_pos += *i;
-        prefix_len += 1;
-        while !pat.is_char_boundary(prefix_len) {
-            prefix_len += 1;
+// Returns the length of the shortest prefix of `text[end..]` not occurring at
+// any position in `begin..end`, so that the suffixes starting in the block
+// sort the same when cut after the prefix, or None if there is none. An
+// occurrence may run past `end`.
+fn calc_tail_len(text: &str, begin: usize, end: usize) -> Option<usize> {
+    let pat = &text[end..];
+    let mut occ_pos = begin;
+    let mut prefix_len = 0;
+    loop {
+        prefix_len += pat[prefix_len..].chars().next()?.len_utf8();
+        match text[occ_pos..end + prefix_len].find(&pat[..prefix_len]) {
+            Some(i) if occ_pos + i < end => occ_pos += i,
+            _ => return Some(prefix_len),
         }
     }
-    Some(prefix_len)
 }
 
-fn sort_blocks(text: &str, block_size: u32) -> Result<BinaryHeap<Reverse<Block>>> {
+fn sort_blocks(text: &str, block_size: u32) -> Result<BinaryHeap<Reverse<Block<'_>>>> {
     let mut heap = BinaryHeap::new();
 
     let mut begin = 0;
@@ -154,7 +506,7 @@ fn sort_blocks(text: &str, block_size: u32) -> Result<BinaryHeap<Reverse<Block>>
         let (end, end_with_tail) = if end == text.len() {
             (end, end)
         } else {
-            match calc_tail_len(&text[begin..end], &text[end..]) {
+            match calc_tail_len(text, begin, end) {
                 Some(l) => (end, end + l),
                 None => (text.len(), text.len()),
             }
@@ -173,6 +525,7 @@ fn sort_blocks(text: &str, block_size: u32) -> Result<BinaryHeap<Reverse<Block>>
 
         let block = Block {
             text: &text[begin..],
+            docs: None,
             reader: BufReader::new(file.reopen()?),
             begin,
             front_index: 0,
@@ -185,15 +538,14 @@ fn sort_blocks(text: &str, block_size: u32) -> Result<BinaryHeap<Reverse<Block>>
     Ok(heap)
 }
 
-fn merge_blocks<B, O>(mut heap: BinaryHeap<Reverse<Block>>, mut buffer: B) -> Result<usize>
+fn merge_blocks<B, O>(mut heap: BinaryHeap<Reverse<Block<'_>>>, mut buffer: B) -> Result<usize>
 where
     B: IntBuffer<u32, O>,
     O: ByteOrder,
 {
     let mut num_written = 0;
     while let Some(Reverse(block)) = heap.pop() {
-        let idx = block.front_index + block.begin as u32;
-        buffer.write(idx)?;
+        buffer.write(block.front_position())?;
         num_written += 1;
 
         if let Some(next) = block.next() {
@@ -206,13 +558,19 @@ where
 
 #[cfg(test)]
 mod tests {
-    use crate::build::{build_suffix_array, VecWrapper};
+    use crate::build::{
+        build_inverse_suffix_array, build_sparse_suffix_array, build_suffix_array,
+        sort_doc_suffixes, VecWrapper,
+    };
+    use crate::index::Docs;
+    use crate::test_util::to_small_alphabet;
+    use crate::SuffixStarts;
     use itertools::Itertools;
     use quickcheck::TestResult;
 
     fn check_suffix_array(text: &str, suffix_array: &[u32]) {
         let actual = suffix_array.iter().sorted().map(|x| *x as usize);
-        let expected = (0..text.len()).filter(|&x| text.is_char_boundary(x as usize));
+        let expected = (0..text.len()).filter(|&x| text.is_char_boundary(x));
         assert!(actual.eq(expected));
 
         let sorted = suffix_array
@@ -238,6 +596,64 @@ mod tests {
 
         check_suffix_array(&text, &buf_a.0);
 
+        let mut inverse = VecWrapper(Vec::new());
+        build_inverse_suffix_array(&text, block_size, &SuffixStarts::All, &mut inverse).unwrap();
+        assert_eq!(inverse.0.len(), text.len());
+        for (rank, &x) in buf_a.0.iter().enumerate() {
+            assert_eq!(inverse.0[x as usize], rank as u32);
+        }
+
         TestResult::passed()
     }
+
+    #[test]
+    fn tail_running_past_block() {
+        // "aaa" after the block "aa" occurs at 1, running past the block
+        let text = "aaaaaba";
+        let mut sa = VecWrapper(Vec::new());
+        build_suffix_array(text, 2, &mut sa).unwrap();
+        check_suffix_array(text, &sa.0);
+    }
+
+    #[quickcheck]
+    fn build_sparse(text: String, block_size: u32) -> TestResult {
+        if block_size == 0 {
+            return TestResult::discard();
+        }
+
+        let starts = SuffixStarts::AfterWhitespace;
+        let mut sparse = VecWrapper(Vec::new());
+        let len = build_sparse_suffix_array(&text, block_size, &starts, &mut sparse).unwrap();
+        assert_eq!(len, sparse.0.len());
+
+        let mut full = VecWrapper(Vec::new());
+        build_suffix_array(&text, u32::MAX, &mut full).unwrap();
+        full.0.retain(|&x| starts.accepts(&text, x as usize));
+        assert_eq!(sparse, full);
+
+        TestResult::passed()
+    }
+
+    #[quickcheck]
+    fn doc_suffix_order(docs: Vec<String>, copies: u8) {
+        // duplicated documents make long runs of equal document suffixes
+        let docs = docs
+            .iter()
+            .map(|doc| to_small_alphabet(doc, "ab"))
+            .flat_map(|doc| std::iter::repeat_n(doc, copies as usize % 4 + 1))
+            .collect::<Vec<_>>();
+        let (text, offsets) = crate::concat_docs(&docs);
+        let docs = Docs {
+            offsets: &offsets,
+            delim_len: 0,
+            text_len: text.len() as u32,
+        };
+
+        let sa = sort_doc_suffixes(&text, &docs, 0..text.len(), &SuffixStarts::All);
+        let mut expected = (0..text.len() as u32)
+            .filter(|&x| docs.suffix_end(x).is_some())
+            .collect::<Vec<_>>();
+        expected.sort_by_key(|&x| (&text[x as usize..docs.suffix_end(x).unwrap() as usize], x));
+        assert_eq!(sa, expected);
+    }
 }
@@ -0,0 +1,141 @@
+use crate::build::IntBuffer;
+use crate::Result;
+use byteorder::NativeEndian;
+use std::collections::BTreeMap;
+use std::io::Write;
+
+// Writes the character preceding each suffix as suffix array positions
+// arrive, skipping the one for the whole text.
+//
+// The text is treated as terminated by a sentinel smaller than any character,
+// so the row of the sentinel comes first and the row of the whole text is
+// where the sentinel would appear in the output.
+pub struct BwtWriter<'a, W: Write> {
+    text: &'a str,
+    writer: W,
+    row: usize,
+    primary_index: usize,
+}
+
+impl<'a, W: Write> BwtWriter<'a, W> {
+    pub fn new(text: &'a str, mut writer: W) -> Result<Self> {
+        if let Some(c) = text.chars().next_back() {
+            write_char(&mut writer, c)?;
+        }
+        Ok(Self {
+            text,
+            writer,
+            row: 1,
+            primary_index: 0,
+        })
+    }
+
+    pub fn primary_index(&self) -> usize {
+        self.primary_index
+    }
+}
+
+impl<W: Write> IntBuffer<u32, NativeEndian> for &mut BwtWriter<'_, W> {
+    fn write(&mut self, n: u32) -> Result<()> {
+        match self.text[..n as usize].chars().next_back() {
+            Some(c) => write_char(&mut self.writer, c)?,
+            None => self.primary_index = self.row,
+        }
+        self.row += 1;
+        Ok(())
+    }
+}
+
+fn write_char<W: Write>(writer: &mut W, c: char) -> Result<()> {
+    let mut buf = [0; 4];
+    writer.write_all(c.encode_utf8(&mut buf).as_bytes())?;
+    Ok(())
+}
+
+/// Reconstructs the text from its Burrows-Wheeler transform as produced by
+/// `IndexBuilder::build_bwt_to_writer`.
+pub fn inverse_bwt(bwt: &str, primary_index: usize) -> Result<String> {
+    let chars = bwt.chars().collect::<Vec<_>>();
+    if primary_index > chars.len() || (primary_index == 0 && !chars.is_empty()) {
+        return Err(crate::Error::InvalidBwt);
+    }
+
+    // number of rows starting with a smaller character, counting the
+    // sentinel row
+    let mut first_rows = BTreeMap::new();
+    for &c in &chars {
+        *first_rows.entry(c).or_insert(0) += 1;
+    }
+    let mut num_rows = 1;
+    for count in first_rows.values_mut() {
+        let n = *count;
+        *count = num_rows;
+        num_rows += n;
+    }
+
+    // LF-mapping of the rows other than the primary one
+    let mut lf = vec![0; chars.len()];
+    for (i, &c) in chars.iter().enumerate() {
+        let next = first_rows.get_mut(&c).unwrap();
+        lf[i] = *next;
+        *next += 1;
+    }
+
+    // walk from the sentinel row backwards through the text
+    let mut text = Vec::with_capacity(chars.len());
+    let mut row = 0;
+    for _ in 0..chars.len() {
+        let i = match row {
+            r if r == primary_index => return Err(crate::Error::InvalidBwt),
+            r if r < primary_index => r,
+            r => r - 1,
+        };
+        text.push(chars[i]);
+        row = lf[i];
+    }
+    if row != primary_index {
+        return Err(crate::Error::InvalidBwt);
+    }
+
+    Ok(text.into_iter().rev().collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::inverse_bwt;
+    use crate::IndexBuilder;
+    use quickcheck::TestResult;
+
+    #[quickcheck]
+    fn roundtrip(text: String, block_size: u32) -> TestResult {
+        if block_size == 0 {
+            return TestResult::discard();
+        }
+
+        let mut bwt = Vec::new();
+        let primary_index = IndexBuilder::new(&text)
+            .block_size(block_size)
+            .build_bwt_to_writer(&mut bwt)
+            .unwrap();
+        let bwt = String::from_utf8(bwt).unwrap();
+        assert_eq!(bwt.chars().count(), text.chars().count());
+        assert_eq!(inverse_bwt(&bwt, primary_index).unwrap(), text);
+
+        TestResult::passed()
+    }
+
+    #[test]
+    fn banana() {
+        let mut bwt = Vec::new();
+        let primary_index = IndexBuilder::new("banana")
+            .build_bwt_to_writer(&mut bwt)
+            .unwrap();
+        // banana$ -> annb$aa
+        assert_eq!(bwt, b"annbaa");
+        assert_eq!(primary_index, 4);
+
+        assert!(inverse_bwt("annbaa", 0).is_err());
+        assert!(inverse_bwt("annbaa", 7).is_err());
+        assert!(inverse_bwt("aaa", 1).is_err());
+    }
+}
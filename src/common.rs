@@ -0,0 +1,392 @@
+use crate::lcp::common_prefix_len;
+use crate::{Index, MultiDocIndex, MultiDocIndexBuilder, Result};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct CommonSubstring {
+    pub position_a: u32,
+    pub position_b: u32,
+    pub len: u32,
+}
+
+// Suffixes of two texts in a generalized suffix array.
+struct Merged {
+    // (whether the suffix is from the second text, position)
+    suffixes: Vec<(bool, u32)>,
+    lcp: Vec<u32>,
+}
+
+// Positions in a subtree of the suffix tree of Merged, grouped by whether
+// they are in the second text and by the characters preceding them.
+type Groups = HashMap<(bool, Option<char>), Vec<u32>>;
+
+impl<'a, 'b> Index<'a, 'b> {
+    /// Returns an occurrence of each distinct longest substring common to
+    /// both texts.
+    ///
+    /// Common substrings may start anywhere, so both indexes must include
+    /// all suffixes.
+    pub fn longest_common_substrings(&self, other: &Index) -> Result<Vec<CommonSubstring>> {
+        Ok(merge(self, other)?.longest_common_substrings(self.text()))
+    }
+
+    /// Returns maximal exact matches of at least `min_len` bytes, i.e.
+    /// common substrings that cannot be extended to either side.
+    ///
+    /// Both indexes must include all suffixes.
+    pub fn common_substrings(&self, other: &Index, min_len: usize) -> Result<Vec<CommonSubstring>> {
+        Ok(merge(self, other)?.maximal_matches(self.text(), other.text(), min_len))
+    }
+}
+
+impl<'a, 'b> MultiDocIndex<'a, 'b> {
+    /// Same as `Index::longest_common_substrings` between two documents, or
+    /// `None` if either document doesn't exist.
+    pub fn longest_common_substrings(
+        &self,
+        doc_a: u32,
+        doc_b: u32,
+    ) -> Result<Option<Vec<CommonSubstring>>> {
+        let (a, b) = match (self.doc(doc_a), self.doc(doc_b)) {
+            (Some(a), Some(b)) => (a, b),
+            _ => return Ok(None),
+        };
+        Ok(Some(joint(a, b)?.longest_common_substrings(a)))
+    }
+
+    /// Same as `Index::common_substrings` between two documents, or `None`
+    /// if either document doesn't exist.
+    pub fn common_substrings(
+        &self,
+        doc_a: u32,
+        doc_b: u32,
+        min_len: usize,
+    ) -> Result<Option<Vec<CommonSubstring>>> {
+        let (a, b) = match (self.doc(doc_a), self.doc(doc_b)) {
+            (Some(a), Some(b)) => (a, b),
+            _ => return Ok(None),
+        };
+        Ok(Some(joint(a, b)?.maximal_matches(a, b, min_len)))
+    }
+}
+
+impl Merged {
+    fn longest_common_substrings(&self, a: &str) -> Vec<CommonSubstring> {
+        let max_len = self
+            .suffixes
+            .windows(2)
+            .zip(self.lcp.iter().skip(1))
+            .filter(|(w, _)| w[0].0 != w[1].0)
+            .map(|(_, &l)| l)
+            .max()
+            .unwrap_or(0);
+        if max_len == 0 {
+            return Vec::new();
+        }
+
+        let mut substrings = Vec::new();
+        for (i, w) in self.suffixes.windows(2).enumerate() {
+            if w[0].0 != w[1].0 && self.lcp[i + 1] == max_len {
+                substrings.push(common_substring(w[0], w[1], max_len));
+            }
+        }
+        // identical substrings are adjacent in the suffix array
+        substrings.dedup_by_key(|c| &a[c.position_a as usize..(c.position_a + c.len) as usize]);
+        substrings
+    }
+
+    // Traverses the suffix tree bottom-up, where a pair of suffixes from
+    // different texts is a maximal exact match of the length of the node
+    // they branch at unless they are preceded by the same character. Only
+    // the nodes of at least `min_len` keep their positions, and the
+    // positions of smaller subtrees are moved into larger ones.
+    fn maximal_matches(&self, a: &str, b: &str, min_len: usize) -> Vec<CommonSubstring> {
+        let min_len = min_len.max(1) as u32;
+        let mut substrings = Vec::new();
+        let mut add_child = |groups: &mut Groups, mut child: Groups, len: u32| {
+            if len < min_len {
+                groups.clear();
+                return;
+            }
+            for (&(from_b, c), xs) in groups.iter() {
+                for (&(child_from_b, d), ys) in &child {
+                    if from_b != child_from_b && (c.is_none() || c != d) {
+                        for &x in xs {
+                            for &y in ys {
+                                substrings.push(common_substring(
+                                    (from_b, x),
+                                    (child_from_b, y),
+                                    len,
+                                ));
+                            }
+                        }
+                    }
+                }
+            }
+            let size = |groups: &Groups| groups.values().map(Vec::len).sum::<usize>();
+            if size(&child) > size(groups) {
+                std::mem::swap(groups, &mut child);
+            }
+            for (key, positions) in child {
+                groups.entry(key).or_default().extend(positions);
+            }
+        };
+
+        // nodes on the path to the current suffix, and the subtree completed
+        // last, which is yet to be added to its parent
+        let mut stack: Vec<(u32, Groups)> = vec![(0, Groups::new())];
+        let mut pending: Option<Groups> = None;
+        for i in 0..=self.suffixes.len() {
+            let l = self.lcp.get(i).copied().unwrap_or(0);
+            while stack.last().unwrap().0 > l {
+                let (len, mut groups) = stack.pop().unwrap();
+                if let Some(child) = pending.take() {
+                    add_child(&mut groups, child, len);
+                }
+                pending = Some(groups);
+            }
+            if stack.last().unwrap().0 < l {
+                stack.push((l, Groups::new()));
+            }
+            if let Some(child) = pending.take() {
+                let (len, groups) = stack.last_mut().unwrap();
+                add_child(groups, child, *len);
+            }
+
+            if let Some(&(from_b, p)) = self.suffixes.get(i) {
+                let text = if from_b { b } else { a };
+                let c = text[..p as usize].chars().next_back();
+                pending = Some(std::iter::once(((from_b, c), vec![p])).collect());
+            }
+        }
+
+        substrings.sort_unstable();
+        substrings
+    }
+}
+
+fn common_substring(x: (bool, u32), y: (bool, u32), len: u32) -> CommonSubstring {
+    let (a, b) = if x.0 { (y, x) } else { (x, y) };
+    CommonSubstring {
+        position_a: a.1,
+        position_b: b.1,
+        len,
+    }
+}
+
+// Sorts the suffixes of two strings together as if they ended at the ends of
+// the strings.
+fn joint(a: &str, b: &str) -> Result<Merged> {
+    let text = [a, b].concat();
+    let multi_doc_index = MultiDocIndexBuilder::new(&text)
+        .doc_offsets(vec![0, a.len() as u32])
+        .generalized(true)
+        .build()?;
+    let index = multi_doc_index.index();
+    let suffixes = index
+        .suffix_array()
+        .iter()
+        .map(|&p| match p.checked_sub(a.len() as u32) {
+            Some(q) => (true, q),
+            None => (false, p),
+        })
+        .collect();
+    Ok(Merged {
+        suffixes,
+        lcp: index.lcp_array(),
+    })
+}
+
+fn merge(a: &Index, b: &Index) -> Result<Merged> {
+    if a.is_sparse() || b.is_sparse() {
+        return Err(crate::Error::InvalidOption(
+            "common substrings need indexes of all suffixes".to_string(),
+        ));
+    }
+    let (sa_a, sa_b) = (a.suffix_array(), b.suffix_array());
+    let lcp_a = a.lcp_array();
+    let lcp_b = b.lcp_array();
+
+    let len = sa_a.len() + sa_b.len();
+    let mut suffixes = Vec::with_capacity(len);
+    let mut lcp = Vec::with_capacity(len);
+    let (mut i, mut j) = (0, 0);
+    while i < sa_a.len() || j < sa_b.len() {
+        let from_b = i == sa_a.len()
+            || (j < sa_b.len()
+                && a.suffix(sa_a[i])
+                    .as_bytes()
+                    .cmp(b.suffix(sa_b[j]).as_bytes())
+                    == Ordering::Greater);
+        let next = if from_b {
+            (true, sa_b[j])
+        } else {
+            (false, sa_a[i])
+        };
+
+        // adjacent suffixes from the same text are adjacent in its own
+        // suffix array too
+        let l = match suffixes.last() {
+            None => 0,
+            Some(&(true, _)) if from_b => lcp_b[j],
+            Some(&(false, _)) if !from_b => lcp_a[i],
+            Some(&(true, p)) => common_prefix_len(b.suffix(p), a.suffix(next.1)) as u32,
+            Some(&(false, p)) => common_prefix_len(a.suffix(p), b.suffix(next.1)) as u32,
+        };
+        suffixes.push(next);
+        lcp.push(l);
+
+        if from_b {
+            j += 1;
+        } else {
+            i += 1;
+        }
+    }
+
+    Ok(Merged { suffixes, lcp })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CommonSubstring;
+    use crate::test_util::to_small_alphabet;
+    use crate::{concat_docs, IndexBuilder, MultiDocIndexBuilder, SuffixStarts};
+    use quickcheck::TestResult;
+
+    fn boundaries(text: &str) -> Vec<usize> {
+        text.char_indices()
+            .map(|(i, _)| i)
+            .chain(std::iter::once(text.len()))
+            .collect()
+    }
+
+    fn common_substrings_naive(a: &str, b: &str, min_len: usize) -> Vec<CommonSubstring> {
+        let mut substrings = Vec::new();
+        for &i in &boundaries(a)[..boundaries(a).len() - 1] {
+            for &j in &boundaries(b)[..boundaries(b).len() - 1] {
+                let left_a = a[..i].chars().next_back();
+                let left_b = b[..j].chars().next_back();
+                if left_a.is_some() && left_a == left_b {
+                    continue;
+                }
+                let len = super::common_prefix_len(&a[i..], &b[j..]);
+                if len > 0 && len >= min_len {
+                    substrings.push(CommonSubstring {
+                        position_a: i as u32,
+                        position_b: j as u32,
+                        len: len as u32,
+                    });
+                }
+            }
+        }
+        substrings.sort_unstable();
+        substrings
+    }
+
+    #[quickcheck]
+    fn common_substrings(a: String, b: String, min_len: usize) -> TestResult {
+        if a.len() > 30 || b.len() > 30 {
+            return TestResult::discard();
+        }
+        let (a, b) = (to_small_alphabet(&a, "ab_"), to_small_alphabet(&b, "ab_"));
+        let min_len = min_len % 4;
+        let index_a = IndexBuilder::new(&a).build().unwrap();
+        let index_b = IndexBuilder::new(&b).build().unwrap();
+
+        let expected = common_substrings_naive(&a, &b, min_len);
+        assert_eq!(
+            index_a.common_substrings(&index_b, min_len).unwrap(),
+            expected
+        );
+
+        let (text, offsets) = concat_docs(&[a.clone(), b.clone()]);
+        let multi_doc_index = MultiDocIndexBuilder::new(&text)
+            .doc_offsets(offsets)
+            .build()
+            .unwrap();
+        assert_eq!(
+            multi_doc_index.common_substrings(0, 1, min_len).unwrap(),
+            Some(expected)
+        );
+        assert_eq!(
+            multi_doc_index.common_substrings(0, 0, min_len).unwrap(),
+            Some(common_substrings_naive(&a, &a, min_len))
+        );
+
+        let expected = common_substrings_naive(&a, &b, 0);
+        let longest = index_a.longest_common_substrings(&index_b).unwrap();
+        let max_len = expected.iter().map(|c| c.len).max();
+        assert_eq!(longest.iter().map(|c| c.len).max(), max_len);
+        let mut expected_substrings = expected
+            .iter()
+            .filter(|c| Some(c.len) == max_len)
+            .map(|c| &a[c.position_a as usize..(c.position_a + c.len) as usize])
+            .collect::<Vec<_>>();
+        expected_substrings.sort_unstable();
+        expected_substrings.dedup();
+        let mut substrings = longest
+            .iter()
+            .map(|c| {
+                let s = &a[c.position_a as usize..(c.position_a + c.len) as usize];
+                assert_eq!(
+                    s,
+                    &b[c.position_b as usize..(c.position_b + c.len) as usize]
+                );
+                s
+            })
+            .collect::<Vec<_>>();
+        substrings.sort_unstable();
+        assert_eq!(substrings, expected_substrings);
+
+        TestResult::passed()
+    }
+
+    #[test]
+    fn documents() {
+        let text = "the quick brown fox\nthe quick red fox\nlazy dog";
+        let multi_doc_index = MultiDocIndexBuilder::new(text).build().unwrap();
+        assert_eq!(
+            multi_doc_index
+                .longest_common_substrings(0, 1)
+                .unwrap()
+                .unwrap(),
+            [CommonSubstring {
+                position_a: 0,
+                position_b: 0,
+                len: 10
+            }]
+        );
+        assert_eq!(
+            multi_doc_index.common_substrings(0, 1, 4).unwrap().unwrap(),
+            [
+                CommonSubstring {
+                    position_a: 0,
+                    position_b: 0,
+                    len: 10
+                },
+                CommonSubstring {
+                    position_a: 15,
+                    position_b: 13,
+                    len: 4
+                }
+            ]
+        );
+        assert!(multi_doc_index
+            .common_substrings(0, 3, 1)
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn sparse_index() {
+        let text = "ab ab";
+        let sparse = IndexBuilder::new(text)
+            .suffix_starts(SuffixStarts::WordStarts)
+            .build()
+            .unwrap();
+        let full = IndexBuilder::new(text).build().unwrap();
+        assert!(full.common_substrings(&sparse, 1).is_err());
+        assert!(sparse.longest_common_substrings(&full).is_err());
+    }
+}
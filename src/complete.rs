@@ -0,0 +1,273 @@
+use crate::{Index, MultiDocIndex};
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::ops::Range;
+
+// Where a completion ends.
+#[derive(Clone, Copy)]
+pub(crate) enum End<'d> {
+    // before a character other than alphanumeric ones and `_`
+    Word,
+    // before an occurrence of the string
+    Str(&'d str),
+    // at the end of the suffix
+    Suffix,
+}
+
+impl<'a, 'b> Index<'a, 'b> {
+    /// Returns up to `k` most frequent words starting with `prefix`, along
+    /// with their counts.
+    ///
+    /// Every occurrence of `prefix` is extended to the end of the word it is
+    /// in, where words consist of alphanumeric characters and `_`. If
+    /// `prefix` starts with a word character, only occurrences at the start
+    /// of a word count, so that "scar" does not count toward "car".
+    pub fn completions(&self, prefix: &str, k: usize) -> Vec<(&'a str, usize)> {
+        self.completions_until(prefix, k, End::Word)
+    }
+
+    pub(crate) fn completions_until(
+        &self,
+        prefix: &str,
+        k: usize,
+        end: End,
+    ) -> Vec<(&'a str, usize)> {
+        let range = self.range_from(prefix, 0);
+        let word_starts = matches!(end, End::Word) && prefix.starts_with(is_word_char);
+        let mut completions = Vec::new();
+        self.collect_completions(range, prefix.len(), end, word_starts, &mut completions);
+
+        completions.sort_unstable_by_key(|&(s, count)| (Reverse(count), s));
+        completions.truncate(k);
+        completions
+    }
+
+    // Traverses the subtrees of the suffix tree below `range` at `depth`
+    // with an explicit stack, as shared continuations can be as long as the
+    // text. If `word_starts` is set, only suffixes not preceded by a word
+    // character are counted.
+    fn collect_completions(
+        &self,
+        range: Range<usize>,
+        depth: usize,
+        end: End,
+        word_starts: bool,
+        completions: &mut Vec<(&'a str, usize)>,
+    ) {
+        let sa = self.suffix_array();
+        let counted = |range: Range<usize>| {
+            if !word_starts {
+                return range.len();
+            }
+            sa[range]
+                .iter()
+                .filter(|&&p| !self.prefix(p).ends_with(is_word_char))
+                .count()
+        };
+
+        let mut stack = vec![(range, depth)];
+        while let Some((mut range, depth)) = stack.pop() {
+            if range.is_empty() {
+                continue;
+            }
+            let first = self.suffix(sa[range.start]);
+
+            // a single suffix is cheaper to extend by scanning the text
+            if range.len() == 1 {
+                if counted(range) == 0 {
+                    continue;
+                }
+                let rest = &first[depth..];
+                let len = match end {
+                    End::Word => rest.find(|c| !is_word_char(c)),
+                    End::Str(s) => rest.find(s),
+                    End::Suffix => None,
+                };
+                completions.push((&first[..depth + len.unwrap_or(rest.len())], 1));
+                continue;
+            }
+
+            // suffixes reaching the end of the text, or of their documents,
+            // come first
+            let mut count = 0;
+            while !range.is_empty() && self.suffix(sa[range.start]).len() == depth {
+                count += counted(range.start..range.start + 1);
+                range.start += 1;
+            }
+            for (c, child) in self.children(range, depth) {
+                let next_depth = depth + c.len_utf8();
+                match end {
+                    End::Word if !is_word_char(c) => count += counted(child),
+                    End::Str(s) if s.starts_with(c) => {
+                        // only some of the suffixes may continue with the
+                        // rest of the string
+                        let ending = self.narrow(child.clone(), depth, s);
+                        count += ending.len();
+                        stack.push((child.start..ending.start, next_depth));
+                        stack.push((ending.end..child.end, next_depth));
+                    }
+                    _ => stack.push((child, next_depth)),
+                }
+            }
+            if count > 0 {
+                completions.push((&first[..depth], count));
+            }
+        }
+    }
+}
+
+impl<'a, 'b> MultiDocIndex<'a, 'b> {
+    /// Returns up to `k` most frequent continuations of `prefix` to the end
+    /// of the document, along with their counts.
+    pub fn completions(&self, prefix: &str, k: usize) -> Vec<(&'a str, usize)> {
+        if !self.may_span_docs(prefix) {
+            // suffixes end at the ends of documents if not delimited
+            let end = match self.delimiter() {
+                "" => End::Suffix,
+                delimiter => End::Str(delimiter),
+            };
+            return self.index().completions_until(prefix, k, end);
+        }
+
+        // occurrences have to be checked one by one as some of them may span
+        // documents
+        let mut counts = HashMap::new();
+        for (doc_id, pos) in self.doc_positions(prefix) {
+            let doc = self.doc(doc_id).unwrap();
+            *counts.entry(&doc[pos as usize..]).or_insert(0) += 1;
+        }
+        let mut completions = counts.into_iter().collect::<Vec<_>>();
+        completions.sort_unstable_by_key(|&(s, count)| (Reverse(count), s));
+        completions.truncate(k);
+        completions
+    }
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+#[cfg(test)]
+mod tests {
+    use super::is_word_char;
+    use crate::test_util::to_small_alphabet;
+    use crate::{concat_docs, IndexBuilder, MultiDocIndexBuilder};
+    use itertools::Itertools;
+    use std::cmp::Reverse;
+    use std::collections::HashMap;
+
+    fn completions_naive<'a, F>(
+        text: &'a str,
+        prefix: &str,
+        find_end: F,
+        word_starts: bool,
+    ) -> Vec<(&'a str, usize)>
+    where
+        F: Fn(&str) -> Option<usize>,
+    {
+        let mut counts = HashMap::new();
+        let occurrences = text.char_indices().filter(|&(i, _)| {
+            text[i..].starts_with(prefix) && !(word_starts && text[..i].ends_with(is_word_char))
+        });
+        for (i, _) in occurrences {
+            let rest = &text[i + prefix.len()..];
+            let end = find_end(rest).unwrap_or(rest.len());
+            *counts.entry(&text[i..i + prefix.len() + end]).or_insert(0) += 1;
+        }
+        counts
+            .into_iter()
+            .sorted_by_key(|&(s, count)| (Reverse(count), s))
+            .collect()
+    }
+
+    fn find_word_end(s: &str) -> Option<usize> {
+        s.find(|c: char| !c.is_alphanumeric() && c != '_')
+    }
+
+    #[quickcheck]
+    fn completions(text: String) {
+        let text = to_small_alphabet(&text, "ab _");
+        let index = IndexBuilder::new(&text).build().unwrap();
+
+        for prefix in &["a", "b", "ab", " a"] {
+            let word_starts = !prefix.starts_with(' ');
+            let expected = completions_naive(&text, prefix, find_word_end, word_starts);
+            assert_eq!(index.completions(prefix, usize::MAX), expected);
+            assert_eq!(
+                index.completions(prefix, 2),
+                &expected[..expected.len().min(2)]
+            );
+        }
+        assert!(index.completions("", 10).is_empty());
+    }
+
+    #[test]
+    fn words() {
+        let text = "car cart card car, care cars scar car_1";
+        let index = IndexBuilder::new(text).build().unwrap();
+        assert_eq!(
+            index.completions("car", 3),
+            [("car", 2), ("car_1", 1), ("card", 1)]
+        );
+    }
+
+    #[test]
+    fn long_continuation() {
+        // each suffix branches off one character deeper than the previous one
+        let text = "a".repeat(100_000);
+        let index = IndexBuilder::new(&text).build().unwrap();
+        assert_eq!(index.completions("a", 2), [(&text[..], 1)]);
+    }
+
+    #[quickcheck]
+    fn multi_doc_completions(texts: Vec<String>) {
+        let text = texts.iter().join("\n");
+        let multi_doc_index = MultiDocIndexBuilder::new(&text).build().unwrap();
+
+        for prefix in texts.iter().filter_map(|t| t.chars().next()) {
+            if prefix == '\n' {
+                continue;
+            }
+            let prefix = prefix.to_string();
+            let expected = completions_naive(&text, &prefix, |s| s.find('\n'), false);
+            assert_eq!(multi_doc_index.completions(&prefix, usize::MAX), expected);
+        }
+        assert!(multi_doc_index.completions("a\nb", 10).is_empty());
+    }
+
+    #[quickcheck]
+    fn string_delimiter(texts: Vec<String>, generalized: bool, explicit: bool) {
+        let texts = texts
+            .iter()
+            .map(|t| to_small_alphabet(t, "abc"))
+            .filter(|t| !t.contains("ab"))
+            .collect::<Vec<_>>();
+        let (text, offsets) = if explicit {
+            concat_docs(&texts)
+        } else {
+            (texts.iter().join("ab"), Vec::new())
+        };
+        let mut builder = MultiDocIndexBuilder::new(&text);
+        builder.generalized(generalized);
+        if explicit {
+            builder.doc_offsets(offsets);
+        } else {
+            builder.delimiter("ab");
+        }
+        let multi_doc_index = builder.build().unwrap();
+
+        for prefix in &["a", "b", "c", "ca", "bc"] {
+            let mut counts = HashMap::new();
+            for t in &texts {
+                for (i, _) in t.match_indices(prefix) {
+                    *counts.entry(&t[i..]).or_insert(0) += 1;
+                }
+            }
+            let expected = counts
+                .into_iter()
+                .sorted_by_key(|&(s, count)| (Reverse(count), s))
+                .collect::<Vec<_>>();
+            assert_eq!(multi_doc_index.completions(prefix, usize::MAX), expected);
+        }
+    }
+}
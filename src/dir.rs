@@ -0,0 +1,188 @@
+use crate::{DocMeta, Result};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use std::fs;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+/// Files under a directory concatenated into a text, to be indexed with
+/// `MultiDocIndexBuilder::doc_offsets` and `MultiDocIndexBuilder::doc_meta`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DirDocs {
+    pub text: String,
+    /// Offsets at which the files start in `text`.
+    pub offsets: Vec<u32>,
+    /// Metadata of the files, named after their paths relative to the
+    /// directory.
+    pub metas: Vec<DocMeta>,
+}
+
+#[derive(Clone)]
+pub struct DirDocsBuilder {
+    root: PathBuf,
+    include: Vec<String>,
+    exclude: Vec<String>,
+    skipped: Vec<PathBuf>,
+}
+
+impl DirDocsBuilder {
+    pub fn new<P: AsRef<Path>>(root: P) -> Self {
+        Self {
+            root: root.as_ref().to_path_buf(),
+            include: Vec::new(),
+            exclude: Vec::new(),
+            skipped: Vec::new(),
+        }
+    }
+
+    /// Reads only the files whose relative paths match any of the included
+    /// globs. All files are read if no glob is included.
+    pub fn include<S: ToString>(&mut self, glob: S) -> &mut Self {
+        self.include.push(glob.to_string());
+        self
+    }
+
+    /// Skips the files and directories whose relative paths match the glob.
+    pub fn exclude<S: ToString>(&mut self, glob: S) -> &mut Self {
+        self.exclude.push(glob.to_string());
+        self
+    }
+
+    /// Skips the file at `path`, such as one the text or its index is written
+    /// to, which may be under the directory.
+    pub fn skip_file<P: AsRef<Path>>(&mut self, path: P) -> &mut Self {
+        self.skipped.push(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Reads the files in the order of their paths. Files that are not valid
+    /// UTF-8 are skipped.
+    pub fn build(&self) -> Result<DirDocs> {
+        let include = build_glob_set(&self.include)?;
+        let exclude = build_glob_set(&self.exclude)?;
+        let relative = |path: &Path| {
+            path.strip_prefix(&self.root)
+                .unwrap_or(path)
+                .to_string_lossy()
+                .replace(std::path::MAIN_SEPARATOR, "/")
+        };
+
+        // files that don't exist yet cannot be found either
+        let skipped = self
+            .skipped
+            .iter()
+            .filter_map(|path| fs::canonicalize(path).ok())
+            .collect::<Vec<_>>();
+
+        let mut docs = DirDocs::default();
+        let entries = WalkDir::new(&self.root)
+            .sort_by_file_name()
+            .into_iter()
+            .filter_entry(|entry| entry.depth() == 0 || !exclude.is_match(relative(entry.path())));
+        for entry in entries {
+            let entry = entry.map_err(std::io::Error::from)?;
+            if !entry.file_type().is_file() {
+                continue;
+            }
+            let path = relative(entry.path());
+            if !self.include.is_empty() && !include.is_match(&path) {
+                continue;
+            }
+            if !skipped.is_empty() && skipped.contains(&fs::canonicalize(entry.path())?) {
+                continue;
+            }
+            let content = match String::from_utf8(fs::read(entry.path())?) {
+                Ok(content) => content,
+                Err(_) => continue,
+            };
+
+            if docs.text.len() + content.len() > u32::MAX as usize {
+                return Err(crate::Error::TextTooLong);
+            }
+            docs.offsets.push(docs.text.len() as u32);
+            docs.text.push_str(&content);
+            docs.metas.push(DocMeta::with_name(path));
+        }
+        Ok(docs)
+    }
+}
+
+fn build_glob_set(globs: &[String]) -> Result<GlobSet> {
+    let mut builder = GlobSetBuilder::new();
+    for glob in globs {
+        builder.add(Glob::new(glob).map_err(|e| crate::Error::InvalidPattern(e.to_string()))?);
+    }
+    builder
+        .build()
+        .map_err(|e| crate::Error::InvalidPattern(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DirDocsBuilder;
+    use crate::MultiDocIndexBuilder;
+    use std::fs;
+
+    #[test]
+    fn walk() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join("src/nested")).unwrap();
+        fs::create_dir_all(root.join("target")).unwrap();
+        fs::write(root.join("README.md"), "# foo\n").unwrap();
+        fs::write(root.join("src/lib.rs"), "fn foo() {}\n\nfn bar() {}\n").unwrap();
+        fs::write(root.join("src/nested/mod.rs"), "foo").unwrap();
+        fs::write(root.join("src/binary.rs"), [0xff, 0xfe]).unwrap();
+        fs::write(root.join("target/out.rs"), "foo").unwrap();
+
+        let docs = DirDocsBuilder::new(root).build().unwrap();
+        let names = docs
+            .metas
+            .iter()
+            .map(|meta| meta.name.as_deref().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            [
+                "README.md",
+                "src/lib.rs",
+                "src/nested/mod.rs",
+                "target/out.rs"
+            ]
+        );
+
+        let docs = DirDocsBuilder::new(root)
+            .include("*.rs")
+            .exclude("target")
+            .build()
+            .unwrap();
+        let names = docs
+            .metas
+            .iter()
+            .map(|meta| meta.name.as_deref().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["src/lib.rs", "src/nested/mod.rs"]);
+        assert_eq!(docs.text, "fn foo() {}\n\nfn bar() {}\nfoo");
+        assert_eq!(docs.offsets, [0, 25]);
+
+        let multi_doc_index = MultiDocIndexBuilder::new(&docs.text)
+            .doc_offsets(docs.offsets.clone())
+            .doc_meta(docs.metas.clone())
+            .build()
+            .unwrap();
+        assert_eq!(multi_doc_index.freq("foo"), 2);
+        assert_eq!(multi_doc_index.freq("}\nfoo"), 0);
+        assert_eq!(multi_doc_index.doc(0), Some("fn foo() {}\n\nfn bar() {}\n"));
+
+        assert!(DirDocsBuilder::new(root).include("[").build().is_err());
+
+        let docs = DirDocsBuilder::new(root)
+            .include("*.rs")
+            .skip_file(root.join("src/lib.rs"))
+            .skip_file(root.join("src/nested/../nested/mod.rs"))
+            .skip_file(root.join("missing.rs"))
+            .build()
+            .unwrap();
+        assert_eq!(docs.metas.len(), 1);
+        assert_eq!(docs.metas[0].name.as_deref(), Some("target/out.rs"));
+    }
+}
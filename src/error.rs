@@ -8,6 +8,12 @@ pub enum Error {
     InvalidOption(String),
     #[error("text is longer than maximum supported length {}", u32::MAX)]
     TextTooLong,
+    #[error("invalid pattern: {0}")]
+    InvalidPattern(String),
+    #[error("invalid Burrows-Wheeler transform or primary index")]
+    InvalidBwt,
     #[error("index is invalid or incompatible with text")]
     InvalidIndex,
+    #[error("index format version {0} is not supported, rebuild the index")]
+    UnsupportedIndexVersion(u32),
 }
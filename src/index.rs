@@ -1,15 +1,68 @@
-use crate::build::{build_suffix_array, IntBuffer, VecWrapper};
-use crate::Result;
+use crate::build::{
+    build_generalized_suffix_array, build_inverse_suffix_array, build_sparse_suffix_array,
+    build_suffix_array, filter_suffixes, invert, invert_external, read_u32s, sort_by_doc_suffix,
+    IntBuffer, VecWrapper,
+};
+use crate::bwt::BwtWriter;
+use crate::listing::{build_doc_listing, DocListing};
+use crate::{meta, DocMeta, Result, SuffixStarts};
 use byteorder::{BigEndian, ByteOrder, LittleEndian, NativeEndian, ReadBytesExt, WriteBytesExt};
 use std::borrow::Cow;
-use std::io::{Cursor, Write};
+use std::cmp::Ordering;
+use std::io::{BufWriter, Cursor, Write};
 use std::mem;
+use std::ops::Range;
 use std::slice::Iter;
+use tempfile::NamedTempFile;
+
+// last word of a serialized MultiDocIndex, "SFMI" in little endian
+const MAGIC: u32 = 0x494d_4653;
+// incremented on incompatible changes to the format
+const VERSION: u32 = 1;
+
+// flags in the footer of a serialized MultiDocIndex
+const GENERALIZED: u32 = 1;
+const DOC_LISTING: u32 = 2;
+const INVERSE_SUFFIX_ARRAY: u32 = 4;
+
+const FOOTER_SIZE: usize = mem::size_of::<u32>()
+    * (
+        // index.suffix_array.len()
+        1
+        // offsets.len()
+        + 1
+        // meta_offsets.len()
+        + 1
+        // delimiter.len()
+        + 1
+        // meta.len()
+        + 1
+        // flags
+        + 1
+        // version
+        + 1
+        // magic
+        + 1
+    );
+// footer of the lengths of the suffix array, offsets and delimiter written
+// before the footer had a magic number
+const LEGACY_FOOTER_SIZE: usize = mem::size_of::<u32>() * 3;
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct Index<'a, 'b> {
     text: &'a str,
     suffix_array: Cow<'b, [u32]>,
+    inverse_suffix_array: Option<Cow<'b, [u32]>>,
+    // set if suffixes end at the ends of documents
+    doc_bounds: Option<DocBounds<'b>>,
+    // whether some character boundaries are not in the suffix array
+    sparse: bool,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct DocBounds<'b> {
+    offsets: Cow<'b, [u32]>,
+    delim_len: u32,
 }
 
 impl<'a, 'b> Index<'a, 'b> {
@@ -21,7 +74,7 @@ impl<'a, 'b> Index<'a, 'b> {
         let suffix_array = if bytes.is_empty() {
             &[]
         } else {
-            bytemuck::try_cast_slice(&bytes).or(Err(crate::Error::InvalidIndex))?
+            bytemuck::try_cast_slice(bytes).or(Err(crate::Error::InvalidIndex))?
         };
         if suffix_array.len() > text.len() {
             return Err(crate::Error::InvalidIndex);
@@ -30,10 +83,33 @@ impl<'a, 'b> Index<'a, 'b> {
         Ok(Index {
             text,
             suffix_array: Cow::Borrowed(suffix_array),
+            inverse_suffix_array: None,
+            doc_bounds: None,
+            sparse: suffix_array.len() < text.chars().count(),
         })
     }
 
-    pub fn text(&self) -> &str {
+    /// Same as `from_bytes`, along with an inverse suffix array written by
+    /// `IndexBuilder::build_inverse_to_writer_*`.
+    pub fn from_bytes_with_inverse(
+        text: &'a str,
+        bytes: &'b [u8],
+        inverse_bytes: &'b [u8],
+    ) -> Result<Index<'a, 'b>> {
+        let mut index = Self::from_bytes(text, bytes)?;
+        let inverse_suffix_array: &[u32] = if inverse_bytes.is_empty() {
+            &[]
+        } else {
+            bytemuck::try_cast_slice(inverse_bytes).or(Err(crate::Error::InvalidIndex))?
+        };
+        if inverse_suffix_array.len() != text.len() {
+            return Err(crate::Error::InvalidIndex);
+        }
+        index.inverse_suffix_array = Some(Cow::Borrowed(inverse_suffix_array));
+        Ok(index)
+    }
+
+    pub fn text(&self) -> &'a str {
         self.text
     }
 
@@ -41,31 +117,180 @@ impl<'a, 'b> Index<'a, 'b> {
         &self.suffix_array
     }
 
+    pub fn inverse_suffix_array(&self) -> Option<&[u32]> {
+        self.inverse_suffix_array.as_deref()
+    }
+
+    /// Returns the rank of the suffix starting at `pos` among the suffixes in
+    /// the suffix array, or `None` if it is not in the suffix array.
+    pub fn rank_of(&self, pos: u32) -> Option<u32> {
+        if self.inverse_suffix_array.is_some() {
+            return self.rank_of_stored(pos);
+        }
+        if pos as usize > self.text.len() || !self.text.is_char_boundary(pos as usize) {
+            return None;
+        }
+        let suffix = self.suffix(pos).as_bytes();
+        self.suffix_array
+            .binary_search_by(|&p| self.suffix(p).as_bytes().cmp(suffix).then(p.cmp(&pos)))
+            .ok()
+            .map(|r| r as u32)
+    }
+
+    /// Compares the suffixes starting at `a` and `b`, in constant time if the
+    /// inverse suffix array is available.
+    pub fn compare_suffixes(&self, a: u32, b: u32) -> Ordering {
+        match (self.rank_of_stored(a), self.rank_of_stored(b)) {
+            (Some(x), Some(y)) => x.cmp(&y),
+            _ => self
+                .suffix(a)
+                .as_bytes()
+                .cmp(self.suffix(b).as_bytes())
+                .then(a.cmp(&b)),
+        }
+    }
+
+    fn rank_of_stored(&self, pos: u32) -> Option<u32> {
+        let inverse = self.inverse_suffix_array.as_ref()?;
+        inverse
+            .get(pos as usize)
+            .copied()
+            .filter(|&r| r != u32::MAX)
+    }
+
+    // Whether suffixes end at the ends of documents, in which case equal
+    // suffixes of different documents are ordered by position.
+    pub(crate) fn is_generalized(&self) -> bool {
+        self.doc_bounds.is_some()
+    }
+
+    // Whether some character boundaries are not in the suffix array.
+    pub(crate) fn is_sparse(&self) -> bool {
+        self.sparse
+    }
+
+    // Whether every character boundary is in the suffix array except those in
+    // delimiters, which a generalized index leaves out.
+    pub(crate) fn has_all_doc_suffixes(&self) -> bool {
+        let docs = match self.docs() {
+            Some(docs) if self.sparse => docs,
+            _ => return !self.sparse,
+        };
+        let delim_chars: usize = docs
+            .offsets
+            .iter()
+            .skip(1)
+            .map(|&offset| {
+                self.text[(offset - docs.delim_len) as usize..offset as usize]
+                    .chars()
+                    .count()
+            })
+            .sum();
+        self.suffix_array.len() + delim_chars == self.text.chars().count()
+    }
+
+    // Ranks of suffixes indexed by position, computed unless stored.
+    pub(crate) fn ranks(&self) -> Cow<'_, [u32]> {
+        match &self.inverse_suffix_array {
+            Some(inverse) => Cow::Borrowed(inverse),
+            None => Cow::Owned(invert(&self.suffix_array, self.text.len())),
+        }
+    }
+
     pub fn freq(&self, query: &str) -> usize {
         self.positions(query).len()
     }
 
+    /// Returns the positions of the occurrences of `query` in suffix array
+    /// order. Only occurrences at positions included by the `SuffixStarts`
+    /// policy the index was built with are returned.
     pub fn positions(&self, query: &str) -> &[u32] {
-        if self.text.is_empty() || query.is_empty() || query.len() > self.text.len() {
-            return &[];
+        &self.suffix_array[self.range_from(query, 0)]
+    }
+
+    // Returns the range of suffixes starting with `query`, looking only at
+    // the suffixes from `from` onward.
+    pub(crate) fn range_from(&self, query: &str, from: usize) -> Range<usize> {
+        let sa = &self.suffix_array[from..];
+        if sa.is_empty() || query.is_empty() || query.len() > self.text.len() {
+            return from..from;
         }
-        let first_suffix = &self.text[self.suffix_array[0] as usize..];
-        let last_suffix = &self.text[self.suffix_array[self.suffix_array.len() - 1] as usize..];
+        let first_suffix = self.suffix(sa[0]);
+        let last_suffix = self.suffix(sa[sa.len() - 1]);
         if (query < first_suffix && !first_suffix.starts_with(query)) || query > last_suffix {
-            return &[];
+            return from..from;
         }
 
-        let start = binary_search(&self.suffix_array, |&i| query <= &self.text[i as usize..]);
-        let end = start
-            + binary_search(&self.suffix_array[start..], |&i| {
-                !self.text[i as usize..].starts_with(query)
-            });
+        let start = binary_search(sa, |&i| query <= self.suffix(i));
+        let end = start + binary_search(&sa[start..], |&i| !self.suffix(i).starts_with(query));
 
-        if start > end {
-            &[]
-        } else {
-            &self.suffix_array[start..end]
+        from + start..from + end
+    }
+
+    // Returns the suffix starting at `pos`, up to the end of its document if
+    // the index is generalized.
+    pub(crate) fn suffix(&self, pos: u32) -> &'a str {
+        match self.docs() {
+            Some(docs) => {
+                let end = docs.suffix_end(pos).unwrap_or(pos);
+                &self.text[pos as usize..end as usize]
+            }
+            None => &self.text[pos as usize..],
+        }
+    }
+
+    // Returns the text preceding `pos`, from the start of its document if the
+    // index is generalized.
+    pub(crate) fn prefix(&self, pos: u32) -> &'a str {
+        match self.docs() {
+            Some(docs) => {
+                let begin = docs.offsets[docs.doc_id(pos).unwrap() as usize];
+                &self.text[begin as usize..pos as usize]
+            }
+            None => &self.text[..pos as usize],
+        }
+    }
+
+    fn docs(&self) -> Option<Docs<'_>> {
+        self.doc_bounds.as_ref().map(|bounds| Docs {
+            offsets: &bounds.offsets,
+            delim_len: bounds.delim_len,
+            text_len: self.text.len() as u32,
+        })
+    }
+
+    // Narrows `range` of suffixes sharing a prefix of `depth` bytes to the ones
+    // continuing with `s`.
+    pub(crate) fn narrow(&self, range: Range<usize>, depth: usize, s: &str) -> Range<usize> {
+        let sa = &self.suffix_array[range.clone()];
+        let rest = |i: u32| &self.suffix(i).as_bytes()[depth..];
+        let start = binary_search(sa, |&i| s.as_bytes() <= rest(i));
+        let end = start + binary_search(&sa[start..], |&i| !rest(i).starts_with(s.as_bytes()));
+        range.start + start..range.start + end
+    }
+
+    // Splits `range` of suffixes sharing a prefix of `depth` bytes by the
+    // character following the prefix. Suffixes ending at `depth` are skipped.
+    pub(crate) fn children(&self, range: Range<usize>, depth: usize) -> Vec<(char, Range<usize>)> {
+        let mut children = Vec::new();
+        let mut start = range.start;
+        while start < range.end {
+            let c = match self.suffix(self.suffix_array[start])[depth..]
+                .chars()
+                .next()
+            {
+                Some(c) => c,
+                None => {
+                    start += 1;
+                    continue;
+                }
+            };
+            let mut buf = [0; 4];
+            let child = self.narrow(start..range.end, depth, c.encode_utf8(&mut buf));
+            start = child.end;
+            children.push((c, child));
         }
+        children
     }
 }
 
@@ -91,6 +316,8 @@ impl<'a, 'b> From<Cow<'b, Index<'a, 'b>>> for Index<'a, 'b> {
 pub struct IndexBuilder<'a> {
     text: &'a str,
     block_size: u32,
+    suffix_starts: SuffixStarts,
+    inverse_suffix_array: bool,
 }
 
 impl<'a> IndexBuilder<'a> {
@@ -98,6 +325,8 @@ impl<'a> IndexBuilder<'a> {
         IndexBuilder {
             text,
             block_size: u32::MAX,
+            suffix_starts: SuffixStarts::All,
+            inverse_suffix_array: false,
         }
     }
 
@@ -106,12 +335,32 @@ impl<'a> IndexBuilder<'a> {
         self
     }
 
+    /// Sets which suffixes are included in the suffix array.
+    pub fn suffix_starts(&mut self, suffix_starts: SuffixStarts) -> &mut Self {
+        self.suffix_starts = suffix_starts;
+        self
+    }
+
+    /// Whether `build` also computes the inverse suffix array.
+    pub fn inverse_suffix_array(&mut self, inverse_suffix_array: bool) -> &mut Self {
+        self.inverse_suffix_array = inverse_suffix_array;
+        self
+    }
+
     pub fn build(&self) -> Result<Index<'a, 'static>> {
         let mut sa = VecWrapper(Vec::new());
         self.build_to_buffer(&mut sa)?;
+        let inverse_suffix_array = if self.inverse_suffix_array {
+            Some(Cow::Owned(invert(&sa.0, self.text.len())))
+        } else {
+            None
+        };
         Ok(Index {
             text: self.text,
+            sparse: sa.0.len() < self.text.chars().count(),
             suffix_array: Cow::Owned(sa.0),
+            inverse_suffix_array,
+            doc_bounds: None,
         })
     }
 
@@ -130,11 +379,51 @@ impl<'a> IndexBuilder<'a> {
         Ok(())
     }
 
+    pub fn build_inverse_to_writer_little_endian<W: Write>(&self, writer: W) -> Result<()> {
+        self.build_inverse_to_buffer::<W, LittleEndian>(writer)?;
+        Ok(())
+    }
+
+    pub fn build_inverse_to_writer_big_endian<W: Write>(&self, writer: W) -> Result<()> {
+        self.build_inverse_to_buffer::<W, BigEndian>(writer)?;
+        Ok(())
+    }
+
+    pub fn build_inverse_to_writer_native_endian<W: Write>(&self, writer: W) -> Result<()> {
+        self.build_inverse_to_buffer::<W, NativeEndian>(writer)?;
+        Ok(())
+    }
+
+    /// Writes the Burrows-Wheeler transform of the text terminated by a
+    /// sentinel, omitting the sentinel, and returns the row it would be in.
+    /// The transform always covers all suffixes regardless of
+    /// `suffix_starts`.
+    pub fn build_bwt_to_writer<W: Write>(&self, writer: W) -> Result<usize> {
+        self.check_options()?;
+        let mut bwt_writer = BwtWriter::new(self.text, writer)?;
+        build_suffix_array(self.text, self.block_size, &mut bwt_writer)?;
+        Ok(bwt_writer.primary_index())
+    }
+
     fn build_to_buffer<B, O>(&self, buffer: B) -> Result<usize>
     where
         B: IntBuffer<u32, O>,
         O: ByteOrder,
     {
+        self.check_options()?;
+        build_sparse_suffix_array(self.text, self.block_size, &self.suffix_starts, buffer)
+    }
+
+    fn build_inverse_to_buffer<B, O>(&self, buffer: B) -> Result<usize>
+    where
+        B: IntBuffer<u32, O>,
+        O: ByteOrder,
+    {
+        self.check_options()?;
+        build_inverse_suffix_array(self.text, self.block_size, &self.suffix_starts, buffer)
+    }
+
+    fn check_options(&self) -> Result<()> {
         if self.text.len() > u32::MAX as usize {
             return Err(crate::Error::TextTooLong);
         }
@@ -143,34 +432,71 @@ impl<'a> IndexBuilder<'a> {
                 "block size cannot be 0".to_string(),
             ));
         }
-        build_suffix_array(self.text, self.block_size, buffer)
+        Ok(())
     }
 }
 
 pub struct DocPositions<'a, 'b> {
     iter: Iter<'a, u32>,
-    offsets: &'b [u32],
+    docs: Docs<'b>,
+    len: u32,
 }
 
 impl Iterator for DocPositions<'_, '_> {
     type Item = (u32, u32);
 
     fn next(&mut self) -> Option<(u32, u32)> {
-        while let Some(p) = self.iter.next() {
-            if let Some(doc_id) = self.doc_id_from_pos(*p) {
-                let pos_in_doc = p - self.offsets[doc_id as usize];
-                return Some((doc_id, pos_in_doc));
+        for p in self.iter.by_ref() {
+            if let Some(x) = self.docs.locate(*p, self.len) {
+                return Some(x);
             }
         }
         None
     }
 }
 
-impl DocPositions<'_, '_> {
-    fn doc_id_from_pos(&self, pos: u32) -> Option<u32> {
-        match self.offsets.binary_search(&pos) {
-            Ok(x) => Some(x as u32),
-            Err(x) => Some((x - 1) as u32),
+// Document boundaries of a text.
+#[derive(Clone, Copy)]
+pub(crate) struct Docs<'b> {
+    pub(crate) offsets: &'b [u32],
+    pub(crate) delim_len: u32,
+    pub(crate) text_len: u32,
+}
+
+impl Docs<'_> {
+    pub(crate) fn doc_id(&self, pos: u32) -> Option<u32> {
+        // the last one among empty documents sharing the offset
+        match self.offsets.partition_point(|&offset| offset <= pos) {
+            0 => None,
+            x => Some((x - 1) as u32),
+        }
+    }
+
+    pub(crate) fn end(&self, doc_id: u32) -> u32 {
+        match self.offsets.get(doc_id as usize + 1) {
+            Some(next) => next - self.delim_len,
+            None => self.text_len,
+        }
+    }
+
+    // Returns `(doc_id, position in document)` of a substring, or `None` if
+    // the substring is not within a single document.
+    pub(crate) fn locate(&self, pos: u32, len: u32) -> Option<(u32, u32)> {
+        let doc_id = self.doc_id(pos)?;
+        if pos + len > self.end(doc_id) {
+            return None;
+        }
+        Some((doc_id, pos - self.offsets[doc_id as usize]))
+    }
+
+    // Returns the end of the document containing `pos`, or `None` if `pos` is
+    // in a delimiter or at the end of a document.
+    pub(crate) fn suffix_end(&self, pos: u32) -> Option<u32> {
+        let end = self.end(self.doc_id(pos)?);
+        if pos < end {
+            Some(end)
+        } else {
+            None
         }
     }
 }
@@ -179,7 +505,13 @@ impl DocPositions<'_, '_> {
 pub struct MultiDocIndex<'a, 'b> {
     index: Cow<'b, Index<'a, 'b>>,
     offsets: Cow<'b, [u32]>,
-    delimiter: char,
+    delimiter: Cow<'b, str>,
+    // empty if no metadata is attached
+    meta_offsets: Cow<'b, [u32]>,
+    meta: Cow<'b, [u8]>,
+    pub(crate) listing: Option<DocListing<'b>>,
+    // version of the format the index was read in, 0 for the legacy format
+    version: u32,
 }
 
 impl<'a, 'b> MultiDocIndex<'a, 'b> {
@@ -187,52 +519,140 @@ impl<'a, 'b> MultiDocIndex<'a, 'b> {
         /* format:
                index.suffix_array
                offsets
+               meta_offsets
+               doc listing (if DOC_LISTING flag is set):
+                   prev
+                   prev_mins
+                   rank_offsets
+                   doc_ranks
+               index.inverse_suffix_array (if INVERSE_SUFFIX_ARRAY flag is set)
                delimiter
+               meta
                footer
+
+           Indexes written before the footer had a magic number consist of
+           the suffix array, offsets and delimiter followed by a footer of
+           their lengths, which are still read.
         */
-        const FOOTER_SIZE: usize = mem::size_of::<u32>()
-            * (
-                // index.suffix_array.len()
-                1
-                // offsets.len()
-                + 1
-                // delimiter.len_utf8()
-                + 1
-            );
+
+        let has_magic = bytes.len() >= mem::size_of::<u32>()
+            && NativeEndian::read_u32(&bytes[bytes.len() - mem::size_of::<u32>()..]) == MAGIC;
+        if !has_magic {
+            return Self::from_legacy_bytes(text, bytes);
+        }
+        if bytes.len() < FOOTER_SIZE {
+            return Err(crate::Error::InvalidIndex);
+        }
 
         // footer
         let mut cursor = Cursor::new(&bytes[bytes.len() - FOOTER_SIZE..]);
         let sa_len = cursor.read_u32::<NativeEndian>()? as usize;
         let offsets_len = cursor.read_u32::<NativeEndian>()? as usize;
+        let meta_offsets_len = cursor.read_u32::<NativeEndian>()? as usize;
         let delim_len = cursor.read_u32::<NativeEndian>()? as usize;
+        let meta_len = cursor.read_u32::<NativeEndian>()? as usize;
+        let flags = cursor.read_u32::<NativeEndian>()?;
+        let version = cursor.read_u32::<NativeEndian>()?;
+        if version != VERSION {
+            return Err(crate::Error::UnsupportedIndexVersion(version));
+        }
 
         let sa_size = mem::size_of::<u32>() * sa_len;
         let offsets_size = mem::size_of::<u32>() * offsets_len;
+        let meta_offsets_size = mem::size_of::<u32>() * meta_offsets_len;
         let delim_size = mem::size_of::<u8>() * delim_len;
+        let meta_size = mem::size_of::<u8>() * meta_len;
+        let listing_lens = if flags & DOC_LISTING != 0 {
+            DocListing::section_lens(sa_len, offsets_len)
+        } else {
+            [0; 4]
+        };
+        let listing_size = mem::size_of::<u32>() * listing_lens.iter().sum::<usize>();
+        let inverse_size = if flags & INVERSE_SUFFIX_ARRAY != 0 {
+            mem::size_of::<u32>() * text.len()
+        } else {
+            0
+        };
 
-        if bytes.len() != sa_size + offsets_size + delim_size + FOOTER_SIZE {
+        if bytes.len()
+            != sa_size
+                + offsets_size
+                + meta_offsets_size
+                + listing_size
+                + inverse_size
+                + delim_size
+                + meta_size
+                + FOOTER_SIZE
+        {
             return Err(crate::Error::InvalidIndex);
         }
 
         // body
         let sa_bytes = &bytes[0..sa_size];
-        let index = Index::from_bytes(text, sa_bytes)?;
-
-        let offsets_bytes = &bytes[sa_size..sa_size + offsets_size];
-        let offsets = bytemuck::try_cast_slice(&offsets_bytes)
-            .or_else(|_| Err(crate::Error::InvalidIndex))?;
+        let mut index = Index::from_bytes(text, sa_bytes)?;
 
-        let delim_bytes = &bytes[sa_size + offsets_size..sa_size + offsets_size + delim_size];
-        let delimiter = std::str::from_utf8(&delim_bytes).or(Err(crate::Error::InvalidIndex))?;
+        let mut begin = sa_size;
+        let mut next_section = |size: usize| {
+            let section = &bytes[begin..begin + size];
+            begin += size;
+            section
+        };
 
-        if delimiter.chars().count() != 1 {
+        let offsets = bytemuck::try_cast_slice(next_section(offsets_size))
+            .or(Err(crate::Error::InvalidIndex))?;
+        let meta_offsets: &[u32] = bytemuck::try_cast_slice(next_section(meta_offsets_size))
+            .or(Err(crate::Error::InvalidIndex))?;
+        let mut listing_sections = Vec::new();
+        for &len in &listing_lens {
+            let section: &[u32] =
+                bytemuck::try_cast_slice(next_section(mem::size_of::<u32>() * len))
+                    .or(Err(crate::Error::InvalidIndex))?;
+            listing_sections.push(Cow::Borrowed(section));
+        }
+        let inverse: &[u32] = bytemuck::try_cast_slice(next_section(inverse_size))
+            .or(Err(crate::Error::InvalidIndex))?;
+        let delimiter =
+            std::str::from_utf8(next_section(delim_size)).or(Err(crate::Error::InvalidIndex))?;
+        let meta = next_section(meta_size);
+
+        if !meta_offsets.is_empty()
+            && (meta_offsets.len() != offsets_len + 1
+                || meta_offsets.last() != Some(&(meta_len as u32)))
+        {
             return Err(crate::Error::InvalidIndex);
         }
 
+        let listing = if flags & DOC_LISTING != 0 {
+            let mut sections = listing_sections.into_iter();
+            let mut next = || sections.next().unwrap();
+            Some(DocListing {
+                prev: next(),
+                prev_mins: next(),
+                rank_offsets: next(),
+                doc_ranks: next(),
+            })
+        } else {
+            None
+        };
+
+        if flags & INVERSE_SUFFIX_ARRAY != 0 {
+            index.inverse_suffix_array = Some(Cow::Borrowed(inverse));
+        }
+        if flags & GENERALIZED != 0 {
+            index.doc_bounds = Some(DocBounds {
+                offsets: Cow::Borrowed(offsets),
+                delim_len: delim_len as u32,
+            });
+        }
+
         Ok(MultiDocIndex {
             index: Cow::Owned(index),
-            offsets: Cow::Borrowed(&offsets),
-            delimiter: delimiter.chars().last().unwrap(),
+            offsets: Cow::Borrowed(offsets),
+            delimiter: Cow::Borrowed(delimiter),
+            meta_offsets: Cow::Borrowed(meta_offsets),
+            meta: Cow::Borrowed(meta),
+            listing,
+            version,
         })
     }
 
@@ -240,32 +660,104 @@ impl<'a, 'b> MultiDocIndex<'a, 'b> {
         &self.index
     }
 
+    /// Returns the version of the format the index was read in, which is 0
+    /// for indexes written before the format had a version number, or the
+    /// current version for indexes built in memory.
+    pub fn format_version(&self) -> u32 {
+        self.version
+    }
+
+    // Size in bytes of the footer in the format the index was read in.
+    pub(crate) fn footer_size(&self) -> usize {
+        match self.version {
+            0 => LEGACY_FOOTER_SIZE,
+            _ => FOOTER_SIZE,
+        }
+    }
+
+    // Reads an index written before the footer had a magic number.
+    fn from_legacy_bytes(text: &'a str, bytes: &'b [u8]) -> Result<MultiDocIndex<'a, 'b>> {
+        if bytes.len() < LEGACY_FOOTER_SIZE {
+            return Err(crate::Error::InvalidIndex);
+        }
+
+        let mut cursor = Cursor::new(&bytes[bytes.len() - LEGACY_FOOTER_SIZE..]);
+        let sa_len = cursor.read_u32::<NativeEndian>()? as usize;
+        let offsets_len = cursor.read_u32::<NativeEndian>()? as usize;
+        let delim_len = cursor.read_u32::<NativeEndian>()? as usize;
+
+        let sa_size = mem::size_of::<u32>() * sa_len;
+        let offsets_size = mem::size_of::<u32>() * offsets_len;
+        if bytes.len() != sa_size + offsets_size + delim_len + LEGACY_FOOTER_SIZE {
+            return Err(crate::Error::InvalidIndex);
+        }
+
+        let index = Index::from_bytes(text, &bytes[..sa_size])?;
+        let offsets = bytemuck::try_cast_slice(&bytes[sa_size..sa_size + offsets_size])
+            .or(Err(crate::Error::InvalidIndex))?;
+        let delimiter = std::str::from_utf8(&bytes[sa_size + offsets_size..][..delim_len])
+            .or(Err(crate::Error::InvalidIndex))?;
+
+        Ok(MultiDocIndex {
+            index: Cow::Owned(index),
+            offsets: Cow::Borrowed(offsets),
+            delimiter: Cow::Borrowed(delimiter),
+            meta_offsets: Cow::Borrowed(&[]),
+            meta: Cow::Borrowed(&[]),
+            listing: None,
+            version: 0,
+        })
+    }
+
+    /// Returns the number of occurrences of `query` not spanning multiple
+    /// documents.
     pub fn freq(&self, query: &str) -> usize {
-        if query.contains(self.delimiter) {
-            0
+        self.freq_of(query, self.index.positions(query))
+    }
+
+    pub(crate) fn freq_of(&self, query: &str, positions: &[u32]) -> usize {
+        if self.may_span_docs(query) {
+            self.doc_positions_of(query, positions).count()
         } else {
-            self.index.positions(query).len()
+            positions.len()
         }
     }
 
-    pub fn doc_positions(&self, query: &str) -> DocPositions {
-        if query.contains(self.delimiter) {
-            return DocPositions {
-                iter: [].iter(),
-                offsets: &[],
-            };
-        }
+    pub fn doc_positions(&self, query: &str) -> DocPositions<'_, '_> {
+        self.doc_positions_of(query, self.index.positions(query))
+    }
+
+    pub(crate) fn doc_positions_of<'p>(
+        &self,
+        query: &str,
+        positions: &'p [u32],
+    ) -> DocPositions<'p, '_> {
         DocPositions {
-            iter: self.index.positions(&query).iter(),
-            offsets: &self.offsets,
+            iter: positions.iter(),
+            docs: self.docs(),
+            len: query.len() as u32,
         }
     }
 
+    // An occurrence of `s` can span documents only if `s` shares a character
+    // with the delimiter, or if documents are not delimited at all, unless
+    // suffixes end at the ends of documents.
+    pub(crate) fn may_span_docs(&self, s: &str) -> bool {
+        !self.index.is_generalized()
+            && (self.delimiter.is_empty() || s.chars().any(|c| self.delimiter.contains(c)))
+    }
+
+    /// Returns the string separating documents, which is empty if the index
+    /// was built with explicit document boundaries.
+    pub fn delimiter(&self) -> &str {
+        &self.delimiter
+    }
+
     pub fn num_docs(&self) -> usize {
         self.offsets.len()
     }
 
-    pub fn doc(&self, doc_id: u32) -> Option<&str> {
+    pub fn doc(&self, doc_id: u32) -> Option<&'a str> {
         let doc_id = doc_id as usize;
         if doc_id >= self.offsets.len() {
             return None;
@@ -274,10 +766,42 @@ impl<'a, 'b> MultiDocIndex<'a, 'b> {
         let end = if doc_id == self.offsets.len() - 1 {
             self.index.text().len()
         } else {
-            self.offsets[doc_id + 1] as usize - self.delimiter.len_utf8()
+            self.offsets[doc_id + 1] as usize - self.delimiter.len()
         };
         Some(&self.index.text()[begin..end])
     }
+
+    /// Returns the metadata attached to the document, or `None` if the index
+    /// has no metadata or the document does not exist.
+    pub fn doc_meta(&self, doc_id: u32) -> Option<DocMeta> {
+        let doc_id = doc_id as usize;
+        let begin = *self.meta_offsets.get(doc_id)? as usize;
+        let end = *self.meta_offsets.get(doc_id + 1)? as usize;
+        meta::decode(self.meta.get(begin..end)?)
+    }
+
+    pub(crate) fn meta_size(&self) -> usize {
+        mem::size_of_val(&*self.meta_offsets) + self.meta.len()
+    }
+
+    // Returns `(doc_id, position in document)` of the substring of `len`
+    // bytes at `pos`, or `None` if it spans multiple documents.
+    pub(crate) fn locate(&self, pos: u32, len: u32) -> Option<(u32, u32)> {
+        self.docs().locate(pos, len)
+    }
+
+    // Returns the document containing or preceding the delimiter at `pos`.
+    pub(crate) fn doc_id(&self, pos: u32) -> u32 {
+        self.docs().doc_id(pos).unwrap()
+    }
+
+    pub(crate) fn docs(&self) -> Docs<'_> {
+        Docs {
+            offsets: &self.offsets,
+            delim_len: self.delimiter.len() as u32,
+            text_len: self.index.text().len() as u32,
+        }
+    }
 }
 
 #[derive(Clone)]
@@ -289,16 +813,26 @@ enum IndexSource<'a, 'b> {
 #[derive(Clone)]
 pub struct MultiDocIndexBuilder<'a, 'b> {
     source: IndexSource<'a, 'b>,
-    delimiter: char,
+    boundaries: Boundaries,
+    meta: Option<Vec<DocMeta>>,
     block_size: u32,
+    suffix_starts: SuffixStarts,
+    generalized: bool,
+    doc_listing: bool,
+    inverse_suffix_array: bool,
 }
 
 impl<'a, 'b> MultiDocIndexBuilder<'a, 'b> {
     pub fn new(text: &'a str) -> Self {
         Self {
             source: IndexSource::Text(text),
-            delimiter: '\n',
+            boundaries: Boundaries::Delimiter("\n".to_string()),
+            meta: None,
             block_size: u32::MAX,
+            suffix_starts: SuffixStarts::All,
+            generalized: false,
+            doc_listing: false,
+            inverse_suffix_array: false,
         }
     }
 
@@ -308,8 +842,13 @@ impl<'a, 'b> MultiDocIndexBuilder<'a, 'b> {
     {
         Self {
             source: IndexSource::Index(index.into()),
-            delimiter: '\n',
+            boundaries: Boundaries::Delimiter("\n".to_string()),
+            meta: None,
             block_size: u32::MAX,
+            suffix_starts: SuffixStarts::All,
+            generalized: false,
+            doc_listing: false,
+            inverse_suffix_array: false,
         }
     }
 
@@ -318,32 +857,139 @@ impl<'a, 'b> MultiDocIndexBuilder<'a, 'b> {
         self
     }
 
-    pub fn delimiter(&mut self, delimiter: char) -> &mut Self {
-        self.delimiter = delimiter;
+    /// Sets the string separating documents, which can be a character or a
+    /// string of any length.
+    pub fn delimiter<S: ToString>(&mut self, delimiter: S) -> &mut Self {
+        self.boundaries = Boundaries::Delimiter(delimiter.to_string());
+        self
+    }
+
+    /// Sets the byte offsets at which documents start instead of splitting
+    /// the text at a delimiter. The first offset must be 0 and the offsets
+    /// must be non-decreasing character boundaries of the text.
+    pub fn doc_offsets(&mut self, offsets: Vec<u32>) -> &mut Self {
+        self.boundaries = Boundaries::Offsets(offsets);
+        self
+    }
+
+    /// Sets the lengths of documents in bytes instead of splitting the text
+    /// at a delimiter. The lengths must add up to the length of the text.
+    pub fn doc_lengths<I: IntoIterator<Item = u32>>(&mut self, lengths: I) -> &mut Self {
+        self.boundaries = Boundaries::Lengths(lengths.into_iter().collect());
+        self
+    }
+
+    /// Attaches metadata to documents, one for each document in order.
+    pub fn doc_meta<I: IntoIterator<Item = DocMeta>>(&mut self, meta: I) -> &mut Self {
+        self.meta = Some(meta.into_iter().collect());
+        self
+    }
+
+    /// Same as `IndexBuilder::suffix_starts`. Ignored when building from an
+    /// existing index.
+    pub fn suffix_starts(&mut self, suffix_starts: SuffixStarts) -> &mut Self {
+        self.suffix_starts = suffix_starts;
+        self
+    }
+
+    /// Whether to sort suffixes as if each of them ended at the end of its
+    /// document, leaving out the suffixes starting in delimiters. LCPs,
+    /// repeats and other results of `index()` then stay within documents.
+    pub fn generalized(&mut self, generalized: bool) -> &mut Self {
+        self.generalized = generalized;
+        self
+    }
+
+    /// Whether to store structures with which `MultiDocIndex::docs_containing`,
+    /// `doc_freq` and `top_docs` list documents in time proportional to their
+    /// number rather than that of occurrences. They take about 2 more words
+    /// per suffix.
+    ///
+    /// Documents are only listed this way for patterns whose occurrences
+    /// cannot span documents, so that the index should also be generalized
+    /// when built with explicit document boundaries.
+    pub fn doc_listing(&mut self, doc_listing: bool) -> &mut Self {
+        self.doc_listing = doc_listing;
+        self
+    }
+
+    /// Whether to store the inverse suffix array in the index, as with
+    /// `IndexBuilder::inverse_suffix_array`.
+    pub fn inverse_suffix_array(&mut self, inverse_suffix_array: bool) -> &mut Self {
+        self.inverse_suffix_array = inverse_suffix_array;
         self
     }
 
     pub fn build(&self) -> Result<MultiDocIndex<'a, 'b>> {
-        let (index, text) = match &self.source {
-            IndexSource::Text(text) => {
-                let index = IndexBuilder::new(text)
+        let offsets = self.offsets()?;
+        self.check_source_docs(&offsets)?;
+        let (meta_offsets, meta) = self.encode_meta(offsets.len())?;
+        let mut index = match &self.source {
+            IndexSource::Text(text) if !self.generalized => Cow::Owned(
+                IndexBuilder::new(text)
                     .block_size(self.block_size)
-                    .build()?;
-                (Cow::Owned(index), *text)
+                    .suffix_starts(self.suffix_starts.clone())
+                    .build()?,
+            ),
+            IndexSource::Index(index) if !self.generalized || index.is_generalized() => {
+                index.clone()
+            }
+            _ => {
+                let mut sa = VecWrapper(Vec::new());
+                self.build_generalized(&offsets, &mut sa)?;
+                Cow::Owned(Index {
+                    text: self.text(),
+                    sparse: sa.0.len() < self.text().chars().count(),
+                    suffix_array: Cow::Owned(sa.0),
+                    inverse_suffix_array: None,
+                    doc_bounds: Some(DocBounds {
+                        offsets: Cow::Owned(offsets.clone()),
+                        delim_len: self.delimiter_str().len() as u32,
+                    }),
+                })
             }
-            IndexSource::Index(index) => (index.clone(), index.text()),
         };
 
-        let delim_len = self.delimiter.len_utf8();
-        let offsets = std::iter::once(0).chain(
-            text.match_indices(self.delimiter)
-                .map(|(i, _)| (i + delim_len) as u32),
-        );
+        if self.inverse_suffix_array != index.inverse_suffix_array.is_some() {
+            let inverse = if self.inverse_suffix_array {
+                Some(Cow::Owned(invert(index.suffix_array(), index.text.len())))
+            } else {
+                None
+            };
+            index.to_mut().inverse_suffix_array = inverse;
+        }
+
+        let listing = if self.doc_listing {
+            let sa = index.suffix_array();
+            let mut buffer = VecWrapper(Vec::new());
+            build_doc_listing(
+                || Ok(sa.iter().map(|&x| Ok(x))),
+                &self.docs(&offsets),
+                self.block_size,
+                &mut buffer,
+            )?;
+            let mut sections = buffer.0;
+            let mut next = |len: usize| Cow::Owned(sections.drain(..len).collect());
+            let [prev_len, mins_len, rank_offsets_len, ranks_len] =
+                DocListing::section_lens(sa.len(), offsets.len());
+            Some(DocListing {
+                prev: next(prev_len),
+                prev_mins: next(mins_len),
+                rank_offsets: next(rank_offsets_len),
+                doc_ranks: next(ranks_len),
+            })
+        } else {
+            None
+        };
 
         Ok(MultiDocIndex {
             index,
-            offsets: Cow::Owned(offsets.collect::<Vec<_>>()),
-            delimiter: self.delimiter,
+            offsets: Cow::Owned(offsets),
+            delimiter: Cow::Owned(self.delimiter_str().to_string()),
+            meta_offsets: Cow::Owned(meta_offsets),
+            meta: Cow::Owned(meta),
+            listing,
+            version: VERSION,
         })
     }
 
@@ -366,40 +1012,275 @@ impl<'a, 'b> MultiDocIndexBuilder<'a, 'b> {
     {
         // See MultiDocIndex::from_bytes for format
 
+        let offsets = self.offsets()?;
+        self.check_source_docs(&offsets)?;
+        let (meta_offsets, meta) = self.encode_meta(offsets.len())?;
+
         // body
 
-        let delim_str = self.delimiter.to_string();
+        let delim_str = self.delimiter_str();
 
-        let (sa_len, text) = match &self.source {
-            IndexSource::Text(text) => {
-                let sa_len = build_suffix_array::<_, O>(text, self.block_size, &mut writer)?;
-                (sa_len, *text)
-            }
-            IndexSource::Index(index) => {
-                for x in index.suffix_array().iter() {
-                    writer.write_u32::<O>(*x)?;
+        // the suffix array is read again to build the doc listing and the
+        // inverse suffix array
+        let sa_file = if self.doc_listing || self.inverse_suffix_array {
+            Some(NamedTempFile::new()?)
+        } else {
+            None
+        };
+        let sa_len = match &sa_file {
+            Some(file) => {
+                let mut file_writer = BufWriter::new(file);
+                let sa_len =
+                    self.build_suffix_array::<_, NativeEndian>(&offsets, &mut file_writer)?;
+                file_writer.flush()?;
+                for x in read_u32s(file)? {
+                    writer.write_u32::<O>(x?)?;
                 }
-                (index.suffix_array().len(), index.text())
+                sa_len
             }
+            None => self.build_suffix_array::<_, O>(&offsets, &mut writer)?,
         };
+        let is_generalized = match &self.source {
+            IndexSource::Index(index) => self.generalized || index.is_generalized(),
+            IndexSource::Text(_) => self.generalized,
+        };
+
+        for offset in offsets.iter().chain(&meta_offsets) {
+            writer.write_u32::<O>(*offset)?;
+        }
 
-        writer.write_u32::<O>(0)?;
-        let mut offsets_len = 1;
-        for (i, _) in text.match_indices(self.delimiter) {
-            writer.write_u32::<O>((i + delim_str.len()) as u32)?;
-            offsets_len += 1;
+        if let Some(file) = sa_file.as_ref().filter(|_| self.doc_listing) {
+            build_doc_listing::<_, _, _, O>(
+                || read_u32s(file),
+                &self.docs(&offsets),
+                self.block_size,
+                &mut writer,
+            )?;
+        }
+        if let Some(file) = sa_file.as_ref().filter(|_| self.inverse_suffix_array) {
+            invert_external::<_, _, _, O>(
+                || read_u32s(file),
+                self.text().len(),
+                self.block_size,
+                &mut writer,
+            )?;
         }
 
         writer.write_all(delim_str.as_bytes())?;
+        writer.write_all(&meta)?;
 
         // footer
 
         writer.write_u32::<O>(sa_len as u32)?;
-        writer.write_u32::<O>(offsets_len as u32)?;
+        writer.write_u32::<O>(offsets.len() as u32)?;
+        writer.write_u32::<O>(meta_offsets.len() as u32)?;
         writer.write_u32::<O>(delim_str.len() as u32)?;
+        writer.write_u32::<O>(meta.len() as u32)?;
+        let mut flags = 0;
+        if is_generalized {
+            flags |= GENERALIZED;
+        }
+        if self.doc_listing {
+            flags |= DOC_LISTING;
+        }
+        if self.inverse_suffix_array {
+            flags |= INVERSE_SUFFIX_ARRAY;
+        }
+        writer.write_u32::<O>(flags)?;
+        writer.write_u32::<O>(VERSION)?;
+        writer.write_u32::<O>(MAGIC)?;
 
         Ok(())
     }
+
+    fn build_suffix_array<B, O>(&self, offsets: &[u32], mut buffer: B) -> Result<usize>
+    where
+        B: IntBuffer<u32, O>,
+        O: ByteOrder,
+    {
+        match &self.source {
+            _ if self.generalized => self.build_generalized(offsets, buffer),
+            IndexSource::Text(text) => {
+                build_sparse_suffix_array(text, self.block_size, &self.suffix_starts, buffer)
+            }
+            IndexSource::Index(index) => {
+                for &x in index.suffix_array() {
+                    buffer.write(x)?;
+                }
+                Ok(index.suffix_array().len())
+            }
+        }
+    }
+
+    fn build_generalized<B, O>(&self, offsets: &[u32], mut buffer: B) -> Result<usize>
+    where
+        B: IntBuffer<u32, O>,
+        O: ByteOrder,
+    {
+        let docs = self.docs(offsets);
+        match &self.source {
+            IndexSource::Text(text) => {
+                IndexBuilder::new(text)
+                    .block_size(self.block_size)
+                    .check_options()?;
+                build_generalized_suffix_array(
+                    text,
+                    &docs,
+                    self.block_size,
+                    &self.suffix_starts,
+                    buffer,
+                )
+            }
+            // checked to have the same documents
+            IndexSource::Index(index) if index.is_generalized() => {
+                for &x in index.suffix_array() {
+                    buffer.write(x)?;
+                }
+                Ok(index.suffix_array().len())
+            }
+            IndexSource::Index(index) => {
+                let (mut sa, lcp) = filter_suffixes(
+                    index.suffix_array().iter().copied(),
+                    index.lcp_array(),
+                    |x| docs.suffix_end(x).is_some(),
+                );
+                sort_by_doc_suffix(&mut sa, &lcp, &docs);
+                for &x in &sa {
+                    buffer.write(x)?;
+                }
+                Ok(sa.len())
+            }
+        }
+    }
+
+    // A generalized index orders suffixes by its documents, so that it can
+    // only be used for the same documents.
+    fn check_source_docs(&self, offsets: &[u32]) -> Result<()> {
+        if let IndexSource::Index(index) = &self.source {
+            if let Some(bounds) = &index.doc_bounds {
+                if *bounds.offsets != *offsets
+                    || bounds.delim_len != self.delimiter_str().len() as u32
+                {
+                    return Err(crate::Error::InvalidOption(
+                        "generalized index has different documents".to_string(),
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn docs<'o>(&self, offsets: &'o [u32]) -> Docs<'o> {
+        Docs {
+            offsets,
+            delim_len: self.delimiter_str().len() as u32,
+            text_len: self.text().len() as u32,
+        }
+    }
+
+    fn text(&self) -> &'a str {
+        match &self.source {
+            IndexSource::Text(text) => text,
+            IndexSource::Index(index) => index.text(),
+        }
+    }
+
+    fn delimiter_str(&self) -> &str {
+        match &self.boundaries {
+            Boundaries::Delimiter(delimiter) => delimiter,
+            _ => "",
+        }
+    }
+
+    // Returns the offsets at which documents start after validating them.
+    fn offsets(&self) -> Result<Vec<u32>> {
+        let text = self.text();
+        let offsets = match &self.boundaries {
+            Boundaries::Delimiter(delimiter) => {
+                if delimiter.is_empty() {
+                    return Err(crate::Error::InvalidOption(
+                        "delimiter cannot be empty".to_string(),
+                    ));
+                }
+                return Ok(std::iter::once(0)
+                    .chain(
+                        text.match_indices(&**delimiter)
+                            .map(|(i, _)| (i + delimiter.len()) as u32),
+                    )
+                    .collect());
+            }
+            Boundaries::Offsets(offsets) => offsets.clone(),
+            Boundaries::Lengths(lengths) => {
+                let total = lengths.iter().map(|&len| len as u64).sum::<u64>();
+                if total != text.len() as u64 {
+                    return Err(crate::Error::InvalidOption(
+                        "document lengths must add up to text length".to_string(),
+                    ));
+                }
+                lengths
+                    .iter()
+                    .scan(0, |offset, &len| {
+                        let begin = *offset;
+                        *offset += len;
+                        Some(begin)
+                    })
+                    .collect()
+            }
+        };
+
+        if offsets.first() != Some(&0) {
+            return Err(crate::Error::InvalidOption(
+                "first document offset must be 0".to_string(),
+            ));
+        }
+        let is_valid = offsets.windows(2).all(|w| w[0] <= w[1])
+            && offsets
+                .iter()
+                .all(|&offset| text.is_char_boundary(offset as usize));
+        if !is_valid {
+            return Err(crate::Error::InvalidOption(
+                "document offsets must be non-decreasing character boundaries of text".to_string(),
+            ));
+        }
+        Ok(offsets)
+    }
+
+    fn encode_meta(&self, num_docs: usize) -> Result<(Vec<u32>, Vec<u8>)> {
+        match &self.meta {
+            Some(metas) if metas.len() != num_docs => Err(crate::Error::InvalidOption(
+                "number of document metadata must equal number of documents".to_string(),
+            )),
+            Some(metas) => meta::encode(metas),
+            None => Ok((Vec::new(), Vec::new())),
+        }
+    }
+}
+
+#[derive(Clone)]
+enum Boundaries {
+    Delimiter(String),
+    Offsets(Vec<u32>),
+    Lengths(Vec<u32>),
+}
+
+/// Concatenates documents into a text along with the offsets at which they
+/// start, to be passed to `MultiDocIndexBuilder::doc_offsets`. No documents
+/// result in a single empty one.
+pub fn concat_docs<I, S>(docs: I) -> (String, Vec<u32>)
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    let mut text = String::new();
+    let mut offsets = Vec::new();
+    for doc in docs {
+        offsets.push(text.len() as u32);
+        text.push_str(doc.as_ref());
+    }
+    if offsets.is_empty() {
+        offsets.push(0);
+    }
+    (text, offsets)
 }
 
 fn binary_search<T, F>(xs: &[T], mut pred: F) -> usize
@@ -420,9 +1301,17 @@ where
 
 #[cfg(test)]
 mod tests {
-    use crate::{Index, IndexBuilder, MultiDocIndex, MultiDocIndexBuilder};
+    use crate::build::invert;
+    use crate::lcp::common_prefix_len;
+    use crate::test_util::to_small_alphabet;
+    use crate::{
+        concat_docs, DocMeta, Index, IndexBuilder, MultiDocIndex, MultiDocIndexBuilder,
+        SuffixStarts,
+    };
+    use byteorder::{NativeEndian, WriteBytesExt};
     use itertools::Itertools;
     use quickcheck::TestResult;
+    use std::sync::Arc;
 
     fn positions_naive(text: &str, query: &str) -> Vec<usize> {
         if text.len() < query.len() {
@@ -456,14 +1345,14 @@ mod tests {
                 }
                 let query = &text[begin..end];
                 let actual = index
-                    .positions(&query)
+                    .positions(query)
                     .iter()
                     .sorted()
                     .map(|x| *x as usize)
                     .collect::<Vec<_>>();
-                let expected = positions_naive(text, &query);
+                let expected = positions_naive(text, query);
                 assert_eq!(actual, expected);
-                assert_eq!(index.freq(&query), expected.len());
+                assert_eq!(index.freq(query), expected.len());
             }
         }
     }
@@ -500,6 +1389,101 @@ mod tests {
         assert_eq!(in_memory, to_writer);
     }
 
+    #[quickcheck]
+    fn sparse_positions(text: String, query: String) {
+        let starts = [
+            SuffixStarts::WordStarts,
+            SuffixStarts::AfterWhitespace,
+            SuffixStarts::LineStarts,
+            SuffixStarts::Custom(Arc::new(|text: &str, pos| text[pos..].starts_with('a'))),
+        ];
+        for starts in &starts {
+            let index = IndexBuilder::new(&text)
+                .suffix_starts(starts.clone())
+                .build()
+                .unwrap();
+            let expected = positions_naive(&text, &query)
+                .into_iter()
+                .filter(|&p| starts.accepts(&text, p))
+                .collect::<Vec<_>>();
+            let actual = index
+                .positions(&query)
+                .iter()
+                .sorted()
+                .map(|x| *x as usize)
+                .collect::<Vec<_>>();
+            if query.is_empty() {
+                assert!(actual.is_empty());
+            } else {
+                assert_eq!(actual, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn word_starts() {
+        let text = "foo_bar baz\nqux(quux) 42";
+        let positions = |starts| {
+            IndexBuilder::new(text)
+                .suffix_starts(starts)
+                .build()
+                .unwrap()
+                .suffix_array()
+                .iter()
+                .copied()
+                .sorted()
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(positions(SuffixStarts::WordStarts), [0, 8, 12, 16, 22]);
+        assert_eq!(positions(SuffixStarts::AfterWhitespace), [0, 8, 12, 22]);
+        assert_eq!(positions(SuffixStarts::LineStarts), [0, 12]);
+    }
+
+    #[quickcheck]
+    fn rank_of(text: String, block_size: u32) -> TestResult {
+        if block_size == 0 {
+            return TestResult::discard();
+        }
+        let in_memory = IndexBuilder::new(&text)
+            .inverse_suffix_array(true)
+            .build()
+            .unwrap();
+        let without_inverse = IndexBuilder::new(&text).build().unwrap();
+
+        let mut buf = Vec::new();
+        IndexBuilder::new(&text)
+            .build_to_writer_native_endian(&mut buf)
+            .unwrap();
+        let mut inverse_buf = Vec::new();
+        IndexBuilder::new(&text)
+            .block_size(block_size)
+            .build_inverse_to_writer_native_endian(&mut inverse_buf)
+            .unwrap();
+        let to_writer = Index::from_bytes_with_inverse(&text, &buf, &inverse_buf).unwrap();
+        assert_eq!(in_memory, to_writer);
+
+        for index in &[in_memory, without_inverse] {
+            for (rank, &p) in index.suffix_array().iter().enumerate() {
+                assert_eq!(index.rank_of(p), Some(rank as u32));
+            }
+            for p in 0..=text.len() as u32 + 1 {
+                if !text.is_char_boundary(p as usize) || p as usize == text.len() {
+                    assert_eq!(index.rank_of(p), None);
+                }
+                for q in 0..text.len() as u32 {
+                    if text.is_char_boundary(p as usize) && text.is_char_boundary(q as usize) {
+                        assert_eq!(
+                            index.compare_suffixes(p, q),
+                            text[p as usize..].cmp(&text[q as usize..])
+                        );
+                    }
+                }
+            }
+        }
+
+        TestResult::passed()
+    }
+
     #[quickcheck]
     fn build_multi_doc_index(texts: Vec<String>, delim: char) {
         let text = texts.iter().join(&delim.to_string());
@@ -535,6 +1519,73 @@ mod tests {
         assert_eq!(new_in_memory, new_to_writer);
     }
 
+    #[quickcheck]
+    fn multi_doc_inverse(texts: Vec<String>, generalized: bool, block_size: u32) {
+        let text = texts.iter().join("\n");
+        let mut builder = MultiDocIndexBuilder::new(&text);
+        builder
+            .generalized(generalized)
+            .inverse_suffix_array(true)
+            .block_size(block_size % 16 + 1);
+        let in_memory = builder.build().unwrap();
+        let mut buf = Vec::new();
+        builder.build_to_writer_native_endian(&mut buf).unwrap();
+        let to_writer = MultiDocIndex::from_bytes(&text, &buf).unwrap();
+        assert_eq!(in_memory, to_writer);
+
+        let index = to_writer.index();
+        assert_eq!(
+            index.inverse_suffix_array(),
+            Some(&invert(index.suffix_array(), text.len())[..])
+        );
+
+        // not kept unless requested
+        let without_inverse = MultiDocIndexBuilder::from_index(index.clone())
+            .generalized(generalized)
+            .build()
+            .unwrap();
+        assert_eq!(without_inverse.index().inverse_suffix_array(), None);
+    }
+
+    #[quickcheck]
+    fn legacy_format(texts: Vec<String>, delim: char) {
+        let text = texts.iter().join(&delim.to_string());
+        let multi_doc_index = MultiDocIndexBuilder::new(&text)
+            .delimiter(delim)
+            .build()
+            .unwrap();
+
+        // suffix array, offsets and delimiter followed by their lengths
+        let mut buf = Vec::new();
+        let sa = multi_doc_index.index().suffix_array();
+        for &x in sa.iter().chain(multi_doc_index.offsets.iter()) {
+            buf.write_u32::<NativeEndian>(x).unwrap();
+        }
+        buf.extend_from_slice(delim.to_string().as_bytes());
+        for &len in &[sa.len(), multi_doc_index.num_docs(), delim.len_utf8()] {
+            buf.write_u32::<NativeEndian>(len as u32).unwrap();
+        }
+        let mut legacy = MultiDocIndex::from_bytes(&text, &buf).unwrap();
+        assert_eq!(legacy.format_version(), 0);
+        assert_eq!(legacy.stats().1.footer_size, 12);
+        legacy.version = super::VERSION;
+        assert_eq!(legacy, multi_doc_index);
+        assert_eq!(multi_doc_index.stats().1.footer_size, 32);
+
+        let mut buf = Vec::new();
+        MultiDocIndexBuilder::new(&text)
+            .delimiter(delim)
+            .build_to_writer_native_endian(&mut buf)
+            .unwrap();
+        // version precedes the magic number
+        let version_pos = buf.len() - 8;
+        buf[version_pos..version_pos + 4].copy_from_slice(&u32::MAX.to_ne_bytes());
+        assert!(matches!(
+            MultiDocIndex::from_bytes(&text, &buf),
+            Err(crate::Error::UnsupportedIndexVersion(u32::MAX))
+        ));
+    }
+
     #[quickcheck]
     fn multi_doc_basic(texts: Vec<String>, delim: char) {
         let delim_str = delim.to_string();
@@ -590,18 +1641,17 @@ mod tests {
                     assert_eq!(0, multi_doc_index.doc_positions(&query_a).count());
                     assert_eq!(0, multi_doc_index.doc_positions(&query_b).count());
 
-                    let actual = multi_doc_index.doc_positions(&query).sorted();
+                    let actual = multi_doc_index.doc_positions(query).sorted();
                     let expected = texts
                         .iter()
                         .enumerate()
-                        .map(|(i, u)| {
-                            positions_naive(u, &query)
+                        .flat_map(|(i, u)| {
+                            positions_naive(u, query)
                                 .into_iter()
                                 .map(|p| (i as u32, p as u32))
                                 .collect::<Vec<_>>()
                                 .into_iter()
                         })
-                        .flatten()
                         .collect::<Vec<_>>();
                     assert_eq!(multi_doc_index.freq(query), expected.len());
                     assert!(actual.eq(expected));
@@ -611,4 +1661,284 @@ mod tests {
 
         TestResult::passed()
     }
+
+    #[quickcheck]
+    fn string_delimiter(texts: Vec<String>, delim_a: char, delim_b: char) -> TestResult {
+        let delim = format!("{}{}", delim_a, delim_b);
+        let text = texts.iter().join(&delim);
+        if text.len() > 100 {
+            return TestResult::discard();
+        }
+        let texts: Vec<&str> = text.split(&delim).collect();
+
+        let multi_doc_index = MultiDocIndexBuilder::new(&text)
+            .delimiter(&delim)
+            .build()
+            .unwrap();
+        let mut buf = Vec::new();
+        MultiDocIndexBuilder::new(&text)
+            .delimiter(&delim)
+            .build_to_writer_native_endian(&mut buf)
+            .unwrap();
+        assert_eq!(
+            multi_doc_index,
+            MultiDocIndex::from_bytes(&text, &buf).unwrap()
+        );
+        assert_eq!(multi_doc_index.delimiter(), delim);
+        assert_eq!(multi_doc_index.num_docs(), texts.len());
+
+        // every substring of the text, including ones overlapping delimiters
+        for end in 1..=text.len() {
+            for begin in 0..end {
+                if !text.is_char_boundary(begin) || !text.is_char_boundary(end) {
+                    continue;
+                }
+                let query = &text[begin..end];
+                let expected = texts
+                    .iter()
+                    .enumerate()
+                    .flat_map(|(i, u)| {
+                        positions_naive(u, query)
+                            .into_iter()
+                            .map(move |p| (i as u32, p as u32))
+                    })
+                    .collect::<Vec<_>>();
+                let actual = multi_doc_index.doc_positions(query).sorted();
+                assert_eq!(multi_doc_index.freq(query), expected.len());
+                assert!(actual.eq(expected));
+            }
+        }
+
+        TestResult::passed()
+    }
+
+    #[test]
+    fn empty_delimiter() {
+        assert!(MultiDocIndexBuilder::new("abc")
+            .delimiter("")
+            .build()
+            .is_err());
+    }
+
+    #[quickcheck]
+    fn explicit_boundaries(docs: Vec<String>) -> TestResult {
+        let (text, offsets) = concat_docs(&docs);
+        if text.len() > 100 {
+            return TestResult::discard();
+        }
+
+        let multi_doc_index = MultiDocIndexBuilder::new(&text)
+            .doc_offsets(offsets.clone())
+            .build()
+            .unwrap();
+        if !docs.is_empty() {
+            assert_eq!(
+                multi_doc_index,
+                MultiDocIndexBuilder::new(&text)
+                    .doc_lengths(docs.iter().map(|doc| doc.len() as u32))
+                    .build()
+                    .unwrap()
+            );
+        }
+        let mut buf = Vec::new();
+        MultiDocIndexBuilder::new(&text)
+            .doc_offsets(offsets)
+            .build_to_writer_native_endian(&mut buf)
+            .unwrap();
+        assert_eq!(
+            multi_doc_index,
+            MultiDocIndex::from_bytes(&text, &buf).unwrap()
+        );
+        assert_eq!(multi_doc_index.delimiter(), "");
+        assert_eq!(multi_doc_index.num_docs(), docs.len().max(1));
+        for (i, doc) in docs.iter().enumerate() {
+            assert_eq!(multi_doc_index.doc(i as u32), Some(doc.as_str()));
+        }
+
+        for end in 1..=text.len() {
+            for begin in 0..end {
+                if !text.is_char_boundary(begin) || !text.is_char_boundary(end) {
+                    continue;
+                }
+                let query = &text[begin..end];
+                let expected = docs
+                    .iter()
+                    .enumerate()
+                    .flat_map(|(i, doc)| {
+                        positions_naive(doc, query)
+                            .into_iter()
+                            .map(move |p| (i as u32, p as u32))
+                    })
+                    .collect::<Vec<_>>();
+                let actual = multi_doc_index.doc_positions(query).sorted();
+                assert_eq!(multi_doc_index.freq(query), expected.len());
+                assert!(actual.eq(expected));
+            }
+        }
+
+        TestResult::passed()
+    }
+
+    #[test]
+    fn invalid_boundaries() {
+        let build = |offsets: Vec<u32>| {
+            MultiDocIndexBuilder::new("a\nbα")
+                .doc_offsets(offsets)
+                .build()
+        };
+        assert!(build(vec![0, 2, 3]).is_ok());
+        assert!(build(vec![0, 0, 5]).is_ok());
+        assert!(build(vec![]).is_err());
+        assert!(build(vec![1]).is_err());
+        assert!(build(vec![0, 3, 2]).is_err());
+        assert!(build(vec![0, 4]).is_err());
+        assert!(build(vec![0, 6]).is_err());
+
+        assert!(MultiDocIndexBuilder::new("abc")
+            .doc_lengths(vec![1, 1])
+            .build()
+            .is_err());
+    }
+
+    #[test]
+    fn doc_meta() {
+        let text = "foo\nbar\nbaz";
+        let metas = vec![
+            DocMeta::with_name("a.txt"),
+            DocMeta::default(),
+            DocMeta {
+                name: Some("c.txt".to_string()),
+                id: Some("42".to_string()),
+                fields: vec![("lang".to_string(), "en".to_string())],
+            },
+        ];
+
+        let multi_doc_index = MultiDocIndexBuilder::new(text)
+            .doc_meta(metas.clone())
+            .build()
+            .unwrap();
+        let mut buf = Vec::new();
+        MultiDocIndexBuilder::new(text)
+            .doc_meta(metas.clone())
+            .build_to_writer_native_endian(&mut buf)
+            .unwrap();
+        assert_eq!(
+            multi_doc_index,
+            MultiDocIndex::from_bytes(text, &buf).unwrap()
+        );
+        for (i, meta) in metas.iter().enumerate() {
+            assert_eq!(multi_doc_index.doc_meta(i as u32).as_ref(), Some(meta));
+        }
+        assert_eq!(multi_doc_index.doc_meta(3), None);
+        assert_eq!(multi_doc_index.doc_meta(2).unwrap().get("lang"), Some("en"));
+
+        let without_meta = MultiDocIndexBuilder::new(text).build().unwrap();
+        assert_eq!(without_meta.doc_meta(0), None);
+
+        assert!(MultiDocIndexBuilder::new(text)
+            .doc_meta(vec![DocMeta::default(); 2])
+            .build()
+            .is_err());
+        assert!(MultiDocIndexBuilder::new(text)
+            .doc_meta(vec![DocMeta::with_name("a\0b"); 3])
+            .build()
+            .is_err());
+    }
+
+    #[quickcheck]
+    fn generalized(texts: Vec<String>, explicit: bool, block_size: u32) -> TestResult {
+        if block_size == 0 {
+            return TestResult::discard();
+        }
+        let texts = texts
+            .iter()
+            .map(|text| to_small_alphabet(text, "ab_"))
+            .collect::<Vec<_>>();
+        let (text, offsets) = if explicit {
+            concat_docs(&texts)
+        } else {
+            (texts.join("ab"), Vec::new())
+        };
+        if text.len() > 100 {
+            return TestResult::discard();
+        }
+        let configure = |builder: &mut MultiDocIndexBuilder| {
+            builder.block_size(block_size % 16 + 1).generalized(true);
+            if explicit {
+                builder.doc_offsets(offsets.clone());
+            } else {
+                builder.delimiter("ab");
+            }
+        };
+
+        let mut builder = MultiDocIndexBuilder::new(&text);
+        configure(&mut builder);
+        let multi_doc_index = builder.build().unwrap();
+        let docs = (0..multi_doc_index.num_docs() as u32)
+            .map(|doc_id| multi_doc_index.doc(doc_id).unwrap())
+            .collect::<Vec<_>>();
+
+        let mut buf = Vec::new();
+        builder.build_to_writer_native_endian(&mut buf).unwrap();
+        assert_eq!(
+            multi_doc_index,
+            MultiDocIndex::from_bytes(&text, &buf).unwrap()
+        );
+        let mut from_index =
+            MultiDocIndexBuilder::from_index(IndexBuilder::new(&text).build().unwrap());
+        configure(&mut from_index);
+        assert_eq!(multi_doc_index, from_index.build().unwrap());
+
+        // each suffix ends at the end of its document, followed by a
+        // terminator unique to the document
+        let mut expected = Vec::new();
+        for (doc, &offset) in docs.iter().zip(multi_doc_index.offsets.iter()) {
+            for (i, _) in doc.char_indices() {
+                expected.push((&doc[i..], offset + i as u32));
+            }
+        }
+        expected.sort();
+        let index = multi_doc_index.index();
+        assert!(index
+            .suffix_array()
+            .iter()
+            .copied()
+            .eq(expected.iter().map(|&(_, p)| p)));
+        for (i, &lcp) in index.lcp_array().iter().enumerate().skip(1) {
+            let (a, b) = (expected[i - 1].0, expected[i].0);
+            assert_eq!(lcp as usize, common_prefix_len(a, b));
+        }
+
+        for end in 1..=text.len() {
+            for begin in 0..end {
+                if !text.is_char_boundary(begin) || !text.is_char_boundary(end) {
+                    continue;
+                }
+                let query = &text[begin..end];
+                let expected = docs
+                    .iter()
+                    .enumerate()
+                    .flat_map(|(i, doc)| {
+                        positions_naive(doc, query)
+                            .into_iter()
+                            .map(move |p| (i as u32, p as u32))
+                    })
+                    .collect::<Vec<_>>();
+                assert_eq!(multi_doc_index.freq(query), expected.len());
+                assert!(multi_doc_index.doc_positions(query).sorted().eq(expected));
+            }
+        }
+
+        // the order of a generalized index only holds for its documents
+        let mut from_generalized = MultiDocIndexBuilder::from_index(index);
+        configure(&mut from_generalized);
+        assert_eq!(multi_doc_index, from_generalized.build().unwrap());
+        assert!(from_generalized
+            .generalized(false)
+            .delimiter("b")
+            .build()
+            .is_err());
+
+        TestResult::passed()
+    }
 }
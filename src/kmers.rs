@@ -0,0 +1,232 @@
+use crate::{Index, MultiDocIndex, Repeat};
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+/// Iterator returned by `Index::kmers`.
+pub struct Kmers<'a, 'i> {
+    index: &'i Index<'a, 'i>,
+    suffix_array: &'i [u32],
+    lcp: Vec<u32>,
+    k: usize,
+    min_count: usize,
+    i: usize,
+}
+
+impl<'a, 'i> Iterator for Kmers<'a, 'i> {
+    type Item = Repeat<'a, 'i>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.i < self.suffix_array.len() {
+            let begin = self.i;
+            let suffix = self.index.suffix(self.suffix_array[begin]);
+            self.i += 1;
+
+            // suffixes shorter than k characters sort before the k-mers they
+            // are prefixes of, so every k-mer occupies a contiguous range
+            let len = match suffix.char_indices().nth(self.k) {
+                Some((len, _)) => len,
+                None if suffix.chars().count() == self.k => suffix.len(),
+                None => continue,
+            };
+            while self.i < self.suffix_array.len() && self.lcp[self.i] as usize >= len {
+                self.i += 1;
+            }
+
+            if self.i - begin >= self.min_count {
+                return Some(Repeat {
+                    substring: &suffix[..len],
+                    positions: &self.suffix_array[begin..self.i],
+                });
+            }
+        }
+        None
+    }
+}
+
+impl<'a, 'b> Index<'a, 'b> {
+    /// Returns an iterator over distinct substrings of `k` characters
+    /// occurring at least `min_count` times, in lexicographic order.
+    pub fn kmers(&self, k: usize, min_count: usize) -> Kmers<'a, '_> {
+        Kmers {
+            index: self,
+            suffix_array: self.suffix_array(),
+            lcp: if k > 0 { self.lcp_array() } else { Vec::new() },
+            k,
+            min_count: min_count.max(1),
+            i: if k > 0 { 0 } else { self.suffix_array().len() },
+        }
+    }
+
+    /// Returns the `n` most frequent substrings of `k` characters, most
+    /// frequent first.
+    pub fn top_kmers(&self, k: usize, n: usize) -> Vec<Repeat<'a, '_>> {
+        let kmers = self.kmers(k, 1).map(|r| (r.substring, r.positions));
+        top(kmers, n)
+            .into_iter()
+            .map(|(substring, positions)| Repeat {
+                substring,
+                positions,
+            })
+            .collect()
+    }
+}
+
+impl<'a, 'b> MultiDocIndex<'a, 'b> {
+    /// Same as `Index::kmers`, counting only the occurrences within
+    /// documents, which are returned along with the substrings in suffix
+    /// array order.
+    pub fn kmers(
+        &self,
+        k: usize,
+        min_count: usize,
+    ) -> impl Iterator<Item = (&'a str, Vec<u32>)> + '_ {
+        let docs = self.docs();
+        let min_count = min_count.max(1);
+        self.index().kmers(k, min_count).filter_map(move |r| {
+            let len = r.substring.len() as u32;
+            let positions = r
+                .positions
+                .iter()
+                .copied()
+                .filter(|&p| docs.locate(p, len).is_some())
+                .collect::<Vec<_>>();
+            if positions.len() >= min_count {
+                Some((r.substring, positions))
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Same as `Index::top_kmers`, counting only the occurrences within
+    /// documents.
+    pub fn top_kmers(&self, k: usize, n: usize) -> Vec<(&'a str, Vec<u32>)> {
+        top(self.kmers(k, 1), n)
+    }
+}
+
+fn top<'a, P, I>(kmers: I, n: usize) -> Vec<(&'a str, P)>
+where
+    P: AsRef<[u32]> + Ord,
+    I: Iterator<Item = (&'a str, P)>,
+{
+    if n == 0 {
+        return Vec::new();
+    }
+
+    // min-heap of the n best so far, ties broken by lexicographic order
+    let mut heap = BinaryHeap::with_capacity(n + 1);
+    for (substring, positions) in kmers {
+        heap.push((Reverse(positions.as_ref().len()), substring, positions));
+        if heap.len() > n {
+            heap.pop();
+        }
+    }
+    heap.into_sorted_vec()
+        .into_iter()
+        .map(|(_, substring, positions)| (substring, positions))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::to_small_alphabet;
+    use crate::{IndexBuilder, MultiDocIndexBuilder};
+    use std::cmp::Reverse;
+    use std::collections::BTreeMap;
+
+    fn kmers_naive(text: &str, k: usize) -> BTreeMap<&str, Vec<u32>> {
+        let boundaries = text
+            .char_indices()
+            .map(|(i, _)| i)
+            .chain(std::iter::once(text.len()))
+            .collect::<Vec<_>>();
+        let mut kmers = BTreeMap::new();
+        for w in boundaries.windows(k + 1) {
+            kmers
+                .entry(&text[w[0]..w[k]])
+                .or_insert_with(Vec::new)
+                .push(w[0] as u32);
+        }
+        kmers
+    }
+
+    #[quickcheck]
+    fn kmers(text: String, k: usize, min_count: usize) {
+        let text = to_small_alphabet(&text, "ab_");
+        let (k, min_count) = (k % 5 + 1, min_count % 4);
+        let index = IndexBuilder::new(&text).build().unwrap();
+
+        let expected = kmers_naive(&text, k)
+            .into_iter()
+            .filter(|(_, ps)| ps.len() >= min_count)
+            .collect::<Vec<_>>();
+        let kmers = index
+            .kmers(k, min_count)
+            .map(|r| {
+                let mut positions = r.positions.to_vec();
+                positions.sort_unstable();
+                (r.substring, positions)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(kmers, expected);
+    }
+
+    #[quickcheck]
+    fn top_kmers(text: String, k: usize, n: usize) {
+        let text = to_small_alphabet(&text, "ab_");
+        let (k, n) = (k % 3 + 1, n % 5);
+        let index = IndexBuilder::new(&text).build().unwrap();
+
+        let mut expected = kmers_naive(&text, k)
+            .into_iter()
+            .map(|(s, ps)| (ps.len(), s))
+            .collect::<Vec<_>>();
+        expected.sort_by_key(|&(count, s)| (Reverse(count), s));
+        expected.truncate(n);
+        let top = index
+            .top_kmers(k, n)
+            .iter()
+            .map(|r| (r.positions.len(), r.substring))
+            .collect::<Vec<_>>();
+        assert_eq!(top, expected);
+    }
+
+    fn counts(kmers: Vec<(&str, Vec<u32>)>) -> Vec<(&str, usize)> {
+        kmers
+            .into_iter()
+            .map(|(kmer, positions)| (kmer, positions.len()))
+            .collect()
+    }
+
+    #[test]
+    fn documents() {
+        let text = "ACGT\nCGTA\nGTAC";
+        let multi_doc_index = MultiDocIndexBuilder::new(text).build().unwrap();
+        let kmers = multi_doc_index.kmers(3, 1).collect::<Vec<_>>();
+        assert_eq!(
+            counts(kmers),
+            [("ACG", 1), ("CGT", 2), ("GTA", 2), ("TAC", 1)]
+        );
+        assert_eq!(counts(multi_doc_index.top_kmers(2, 1)), [("GT", 3)]);
+        assert!(multi_doc_index.kmers(0, 1).next().is_none());
+
+        let text = "ACGT\r\nCGTA\r\nGTAC";
+        let multi_doc_index = MultiDocIndexBuilder::new(text)
+            .delimiter("\r\n")
+            .build()
+            .unwrap();
+        let kmers = multi_doc_index.kmers(3, 2).collect::<Vec<_>>();
+        assert_eq!(counts(kmers), [("CGT", 2), ("GTA", 2)]);
+
+        // "C\r" occurs once within a document and once across documents
+        let text = "AC\r\nC\rA";
+        let multi_doc_index = MultiDocIndexBuilder::new(text)
+            .delimiter("\r\n")
+            .build()
+            .unwrap();
+        let kmers = multi_doc_index.kmers(2, 1).collect::<Vec<_>>();
+        assert_eq!(kmers, [("\rA", vec![5]), ("AC", vec![0]), ("C\r", vec![4])]);
+        assert_eq!(multi_doc_index.top_kmers(2, 1), [("\rA", vec![5])]);
+    }
+}
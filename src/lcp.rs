@@ -0,0 +1,164 @@
+use crate::Index;
+
+impl<'a, 'b> Index<'a, 'b> {
+    /// Returns the longest common prefix lengths of adjacent suffixes.
+    ///
+    /// `lcp[i]` is the length in bytes of the longest common prefix of the
+    /// suffixes at `suffix_array[i - 1]` and `suffix_array[i]`, and `lcp[0]`
+    /// is 0.
+    pub fn lcp_array(&self) -> Vec<u32> {
+        let sa = self.suffix_array();
+        let mut lcp = vec![0; sa.len()];
+
+        if !self.has_all_doc_suffixes() {
+            for i in 1..sa.len() {
+                lcp[i] = common_prefix_len(self.suffix(sa[i - 1]), self.suffix(sa[i])) as u32;
+            }
+            return lcp;
+        }
+
+        // Kasai et al.'s algorithm extended to skip whole characters. Suffixes
+        // cut at the ends of documents keep its invariant, and those starting
+        // in delimiters are not ranked.
+        let rank = self.ranks();
+        let mut h = 0;
+        for (p, c) in self.text().char_indices() {
+            let r = rank[p];
+            if r == u32::MAX {
+                h = 0;
+                continue;
+            }
+            let r = r as usize;
+            if r == 0 {
+                h = 0;
+                continue;
+            }
+            let a = self.suffix(p as u32).as_bytes();
+            let b = self.suffix(sa[r - 1]).as_bytes();
+            while h < a.len() && h < b.len() && a[h] == b[h] {
+                h += 1;
+            }
+            while !self.text().is_char_boundary(p + h) {
+                h -= 1;
+            }
+            lcp[r] = h as u32;
+            h = h.saturating_sub(c.len_utf8());
+        }
+
+        lcp
+    }
+}
+
+// Length in bytes of the longest common prefix that ends at a character
+// boundary.
+pub(crate) fn common_prefix_len(a: &str, b: &str) -> usize {
+    let mut len = a.bytes().zip(b.bytes()).take_while(|(x, y)| x == y).count();
+    while !a.is_char_boundary(len) {
+        len -= 1;
+    }
+    len
+}
+
+// Calls `f(lcp, lb, rb, has_child_interval)` for every lcp-interval
+// `[lb, rb]` in bottom-up order, including the root interval with lcp 0.
+pub(crate) fn for_each_lcp_interval<F>(lcp: &[u32], mut f: F)
+where
+    F: FnMut(u32, usize, usize, bool),
+{
+    struct Frame {
+        lcp: u32,
+        lb: usize,
+        has_child: bool,
+    }
+
+    if lcp.is_empty() {
+        return;
+    }
+
+    let mut stack = vec![Frame {
+        lcp: 0,
+        lb: 0,
+        has_child: false,
+    }];
+    for i in 1..=lcp.len() {
+        let cur = lcp.get(i).copied().unwrap_or(0);
+        let mut lb = i - 1;
+        let mut child = false;
+        while cur < stack.last().unwrap().lcp {
+            let top = stack.pop().unwrap();
+            f(top.lcp, top.lb, i - 1, top.has_child);
+            lb = top.lb;
+            let parent = stack.last_mut().unwrap();
+            if cur <= parent.lcp {
+                parent.has_child = true;
+            } else {
+                child = true;
+            }
+        }
+        if cur > stack.last().unwrap().lcp {
+            stack.push(Frame {
+                lcp: cur,
+                lb,
+                has_child: child,
+            });
+        }
+    }
+
+    let root = stack.pop().unwrap();
+    f(root.lcp, root.lb, lcp.len() - 1, root.has_child);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::common_prefix_len;
+    use crate::{IndexBuilder, MultiDocIndexBuilder, SuffixStarts};
+
+    #[quickcheck]
+    fn lcp_array(text: String) {
+        let index = IndexBuilder::new(&text).build().unwrap();
+        let sa = index.suffix_array();
+        let lcp = index.lcp_array();
+
+        assert_eq!(lcp.len(), sa.len());
+        for i in 1..sa.len() {
+            let a = &text[sa[i - 1] as usize..];
+            let b = &text[sa[i] as usize..];
+            let expected = a
+                .char_indices()
+                .map(|(i, _)| i)
+                .chain(std::iter::once(a.len()))
+                .filter(|&i| b.starts_with(&a[..i]))
+                .max()
+                .unwrap();
+            assert_eq!(lcp[i] as usize, expected);
+        }
+    }
+
+    #[quickcheck]
+    fn generalized(text: String, sparse: bool) {
+        let text = crate::test_util::to_small_alphabet(&text, "ab #_");
+        let mut builder = MultiDocIndexBuilder::new(&text);
+        builder.delimiter("##").generalized(true);
+        if sparse {
+            builder.suffix_starts(SuffixStarts::AfterWhitespace);
+        }
+        let doc_index = builder.build().unwrap();
+        let index = doc_index.index();
+        assert!(sparse || index.has_all_doc_suffixes());
+        let sa = index.suffix_array();
+        let lcp = index.lcp_array();
+
+        assert_eq!(lcp.len(), sa.len());
+        for i in 1..sa.len() {
+            let expected = common_prefix_len(index.suffix(sa[i - 1]), index.suffix(sa[i]));
+            assert_eq!(lcp[i] as usize, expected);
+        }
+    }
+
+    #[test]
+    fn partial_characters() {
+        assert_eq!(common_prefix_len("aé", "aè"), 1);
+        assert_eq!(common_prefix_len("éa", "éb"), 2);
+        assert_eq!(common_prefix_len("", "a"), 0);
+    }
+}
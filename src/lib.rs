@@ -2,11 +2,48 @@
 #[macro_use]
 extern crate quickcheck_macros;
 
+mod approx;
+mod batch;
 mod build;
+mod bwt;
+mod common;
+mod complete;
+mod dir;
 mod error;
 mod index;
+mod kmers;
+mod lcp;
+mod listing;
+mod lz;
+mod matching;
+mod meta;
+mod proximity;
+mod query;
+mod rank;
+mod regex;
+mod repeats;
+mod rmq;
+mod starts;
+mod stats;
+#[cfg(test)]
+mod test_util;
+mod tree;
+mod wildcard;
 
+pub use approx::{ApproxMatch, Distance};
+pub use bwt::inverse_bwt;
+pub use common::CommonSubstring;
+pub use dir::{DirDocs, DirDocsBuilder};
 pub use error::Error;
 pub type Result<T> = std::result::Result<T, error::Error>;
 
-pub use index::{Index, IndexBuilder, MultiDocIndex, MultiDocIndexBuilder};
+pub use index::{concat_docs, Index, IndexBuilder, MultiDocIndex, MultiDocIndexBuilder};
+pub use kmers::Kmers;
+pub use lz::{Factor, Lz77Factors, LzEndFactors};
+pub use matching::Span;
+pub use meta::DocMeta;
+pub use query::Query;
+pub use repeats::Repeat;
+pub use starts::SuffixStarts;
+pub use stats::{DocStats, Stats};
+pub use tree::{Node, Nodes, SuffixTree};
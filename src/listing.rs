@@ -0,0 +1,365 @@
+use crate::build::IntBuffer;
+use crate::index::Docs;
+use crate::rmq::{level_lens, BlockMins, BlockMinsBuilder};
+use crate::{MultiDocIndex, Result};
+use byteorder::{ByteOrder, NativeEndian, ReadBytesExt, WriteBytesExt};
+use std::borrow::Cow;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::io::{self, BufReader, BufWriter, Write};
+use std::mem;
+use std::ops::Range;
+use tempfile::NamedTempFile;
+
+// Structures for listing the documents containing a pattern without
+// enumerating its occurrences, stored in the index.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct DocListing<'b> {
+    // Previous-occurrence array of Muthukrishnan's document listing. For each
+    // rank `i`, it holds one plus the largest rank `j < i` whose suffix is in
+    // the same document, or 0 if there is no such rank.
+    pub(crate) prev: Cow<'b, [u32]>,
+    // levels of BlockMins over `prev`
+    pub(crate) prev_mins: Cow<'b, [u32]>,
+    // offsets of the groups of `doc_ranks`, one for each document and the end
+    pub(crate) rank_offsets: Cow<'b, [u32]>,
+    // ranks of suffixes grouped by document in ascending order
+    pub(crate) doc_ranks: Cow<'b, [u32]>,
+}
+
+impl DocListing<'_> {
+    // Returns the lengths of the sections in the order they are written.
+    pub(crate) fn section_lens(sa_len: usize, num_docs: usize) -> [usize; 4] {
+        let mins_len = level_lens(sa_len).iter().sum();
+        [sa_len, mins_len, num_docs + 1, sa_len]
+    }
+
+    pub(crate) fn size(&self) -> usize {
+        mem::size_of::<u32>()
+            * (self.prev.len()
+                + self.prev_mins.len()
+                + self.rank_offsets.len()
+                + self.doc_ranks.len())
+    }
+}
+
+// Writes the sections of DocListing of a suffix array, which is read in full
+// by each call to `sa`. Ranks grouped by document are scattered to temporary
+// files, one for each group of documents with at most `block_size` suffixes,
+// if they don't fit in a block.
+pub(crate) fn build_doc_listing<S, I, B, O>(
+    mut sa: S,
+    docs: &Docs,
+    block_size: u32,
+    mut buffer: B,
+) -> Result<()>
+where
+    S: FnMut() -> Result<I>,
+    I: Iterator<Item = Result<u32>>,
+    B: IntBuffer<u32, O>,
+    O: ByteOrder,
+{
+    let num_docs = docs.offsets.len();
+    let doc_id = |p: u32| docs.doc_id(p).unwrap() as usize;
+
+    let mut last = vec![0; num_docs];
+    let mut counts = vec![0u32; num_docs];
+    let mut mins = BlockMinsBuilder::default();
+    let mut sa_len = 0;
+    for (i, p) in sa()?.enumerate() {
+        let doc_id = doc_id(p?);
+        let prev = mem::replace(&mut last[doc_id], i as u32 + 1);
+        buffer.write(prev)?;
+        mins.push(prev);
+        counts[doc_id] += 1;
+        sa_len += 1;
+    }
+    drop(last);
+    for x in mins.finish() {
+        buffer.write(x)?;
+    }
+
+    let mut rank_offsets = Vec::with_capacity(num_docs + 1);
+    rank_offsets.push(0);
+    for &count in &counts {
+        rank_offsets.push(rank_offsets.last().unwrap() + count);
+    }
+    for &offset in &rank_offsets {
+        buffer.write(offset)?;
+    }
+
+    if sa_len <= block_size as usize {
+        let mut next = rank_offsets.clone();
+        let mut ranks = vec![0; sa_len];
+        for (i, p) in sa()?.enumerate() {
+            let next = &mut next[doc_id(p?)];
+            ranks[*next as usize] = i as u32;
+            *next += 1;
+        }
+        for x in ranks {
+            buffer.write(x)?;
+        }
+        return Ok(());
+    }
+
+    // groups of consecutive documents, where a document with more suffixes
+    // than `block_size` makes a larger group
+    let mut group_starts = vec![0];
+    for doc_id in 0..num_docs {
+        let start = *group_starts.last().unwrap();
+        if doc_id > start && rank_offsets[doc_id + 1] - rank_offsets[start] > block_size {
+            group_starts.push(doc_id);
+        }
+    }
+    group_starts.push(num_docs);
+
+    let files = (1..group_starts.len())
+        .map(|_| NamedTempFile::new())
+        .collect::<io::Result<Vec<_>>>()?;
+    {
+        let mut writers = files.iter().map(BufWriter::new).collect::<Vec<_>>();
+        for (i, p) in sa()?.enumerate() {
+            let doc_id = doc_id(p?);
+            let group = group_starts.partition_point(|&start| start <= doc_id) - 1;
+            writers[group].write_u32::<NativeEndian>(doc_id as u32)?;
+            writers[group].write_u32::<NativeEndian>(i as u32)?;
+        }
+        for writer in &mut writers {
+            writer.flush()?;
+        }
+    }
+
+    for (file, starts) in files.iter().zip(group_starts.windows(2)) {
+        let begin = rank_offsets[starts[0]];
+        let mut next = rank_offsets[starts[0]..starts[1]]
+            .iter()
+            .map(|offset| offset - begin)
+            .collect::<Vec<_>>();
+        let mut ranks = vec![0; (rank_offsets[starts[1]] - begin) as usize];
+        let mut reader = BufReader::new(file.reopen()?);
+        for _ in 0..ranks.len() {
+            let doc_id = reader.read_u32::<NativeEndian>()? as usize;
+            let rank = reader.read_u32::<NativeEndian>()?;
+            let next = &mut next[doc_id - starts[0]];
+            ranks[*next as usize] = rank;
+            *next += 1;
+        }
+        for x in ranks {
+            buffer.write(x)?;
+        }
+    }
+
+    Ok(())
+}
+
+impl<'a, 'b> MultiDocIndex<'a, 'b> {
+    /// Returns the IDs of documents containing `query` in ascending order.
+    ///
+    /// If the index was built with `MultiDocIndexBuilder::doc_listing`, it
+    /// takes time proportional to the number of the documents rather than
+    /// that of occurrences. Otherwise, or if occurrences of `query` can span
+    /// documents (see `MultiDocIndexBuilder::generalized`), the occurrences
+    /// are enumerated.
+    pub fn docs_containing(&self, query: &str) -> Vec<u32> {
+        let (listing, sa, range) = match self.listing_range(query) {
+            Some(x) => x,
+            None => {
+                let mut doc_ids = self
+                    .doc_positions(query)
+                    .map(|(doc_id, _)| doc_id)
+                    .collect::<Vec<_>>();
+                doc_ids.sort_unstable();
+                doc_ids.dedup();
+                return doc_ids;
+            }
+        };
+
+        let mut doc_ids = first_occurrences(listing, range.clone())
+            .map(|i| self.doc_id(sa[i]))
+            .collect::<Vec<_>>();
+        doc_ids.sort_unstable();
+        doc_ids
+    }
+
+    /// Returns the number of documents containing `query`.
+    pub fn doc_freq(&self, query: &str) -> usize {
+        self.docs_containing(query).len()
+    }
+
+    /// Returns up to `k` pairs of `(doc_id, number of occurrences)` of
+    /// documents containing `query` most often, in descending order of the
+    /// numbers and then ascending order of IDs.
+    ///
+    /// With `MultiDocIndexBuilder::doc_listing`, occurrences are counted per
+    /// document containing `query` without enumerating them, stopping once
+    /// the remaining documents cannot contain `query` more often than the
+    /// `k`-th one. Otherwise they are enumerated as in `docs_containing`.
+    pub fn top_docs(&self, query: &str, k: usize) -> Vec<(u32, usize)> {
+        if k == 0 {
+            return Vec::new();
+        }
+
+        // the k best pairs so far, with the worst on top
+        let mut heap = BinaryHeap::with_capacity(k.min(self.num_docs()) + 1);
+        let push = |heap: &mut BinaryHeap<_>, doc_id: u32, count: usize| {
+            heap.push((Reverse(count), doc_id));
+            if heap.len() > k {
+                heap.pop();
+            }
+        };
+
+        match self.listing_range(query) {
+            Some((listing, sa, range)) => {
+                for i in first_occurrences(listing, range.clone()) {
+                    // documents first occurring at `i` or later occur at
+                    // most `range.end - i` times
+                    if heap.len() == k {
+                        if let Some(&(Reverse(min), _)) = heap.peek() {
+                            if min > range.end - i {
+                                break;
+                            }
+                        }
+                    }
+                    let doc_id = self.doc_id(sa[i]);
+                    let (begin, end) = (
+                        listing.rank_offsets[doc_id as usize] as usize,
+                        listing.rank_offsets[doc_id as usize + 1] as usize,
+                    );
+                    let ranks = &listing.doc_ranks[begin..end];
+                    let count = ranks.partition_point(|&r| (r as usize) < range.end)
+                        - ranks.partition_point(|&r| (r as usize) < i);
+                    push(&mut heap, doc_id, count);
+                }
+            }
+            None => {
+                let mut counts = HashMap::new();
+                for (doc_id, _) in self.doc_positions(query) {
+                    *counts.entry(doc_id).or_insert(0) += 1;
+                }
+                for (doc_id, count) in counts {
+                    push(&mut heap, doc_id, count);
+                }
+            }
+        }
+
+        let mut top = heap
+            .into_iter()
+            .map(|(Reverse(count), doc_id)| (doc_id, count))
+            .collect::<Vec<_>>();
+        top.sort_unstable_by(|(a_id, a), (b_id, b)| b.cmp(a).then(a_id.cmp(b_id)));
+        top
+    }
+
+    // Returns the stored DocListing, the suffix array and the range of
+    // `query` in it if documents can be listed without enumerating
+    // occurrences.
+    fn listing_range(&self, query: &str) -> Option<(&DocListing<'b>, &[u32], Range<usize>)> {
+        let listing = self.listing.as_ref()?;
+        if self.may_span_docs(query) {
+            return None;
+        }
+        let index = self.index();
+        Some((listing, index.suffix_array(), index.range_from(query, 0)))
+    }
+}
+
+// Returns the ranks in `range` that are the first occurrences of their
+// documents in the range, in ascending order. A rank is such one if and only
+// if the previous occurrence is before the range.
+fn first_occurrences<'l>(
+    listing: &'l DocListing<'_>,
+    range: Range<usize>,
+) -> impl Iterator<Item = usize> + 'l {
+    let mins = BlockMins::new(&listing.prev, &listing.prev_mins);
+    let bound = range.start as u32 + 1;
+    let mut i = range.start;
+    std::iter::from_fn(move || {
+        let j = mins.next_less(i, bound).filter(|&j| j < range.end)?;
+        i = j + 1;
+        Some(j)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::to_small_alphabet;
+    use crate::{concat_docs, MultiDocIndex, MultiDocIndexBuilder};
+    use itertools::Itertools;
+    use quickcheck::TestResult;
+    use std::cmp::Reverse;
+    use std::collections::HashMap;
+
+    #[quickcheck]
+    fn docs_containing(
+        texts: Vec<String>,
+        delimited: bool,
+        generalized: bool,
+        doc_listing: bool,
+        block_size: u32,
+    ) -> TestResult {
+        let texts = texts
+            .iter()
+            .map(|text| to_small_alphabet(text, "ab_"))
+            .collect::<Vec<_>>();
+        let (text, offsets) = concat_docs(&texts);
+        let joined = texts.join("b");
+        if text.len() > 100 {
+            return TestResult::discard();
+        }
+
+        let mut builder = if delimited {
+            let mut builder = MultiDocIndexBuilder::new(&joined);
+            builder.delimiter("b");
+            builder
+        } else {
+            let mut builder = MultiDocIndexBuilder::new(&text);
+            builder.doc_offsets(offsets);
+            builder
+        };
+        builder
+            .generalized(generalized)
+            .doc_listing(doc_listing)
+            .block_size(block_size % 16 + 1);
+        let multi_doc_index = builder.build().unwrap();
+        let text = multi_doc_index.index().text();
+
+        let mut bytes = Vec::new();
+        builder.build_to_writer_native_endian(&mut bytes).unwrap();
+        assert_eq!(
+            MultiDocIndex::from_bytes(text, &bytes).unwrap(),
+            multi_doc_index
+        );
+
+        for end in 0..=text.len() {
+            for begin in 0..=end {
+                if !text.is_char_boundary(begin) || !text.is_char_boundary(end) {
+                    continue;
+                }
+                let query = &text[begin..end];
+                let expected = multi_doc_index
+                    .doc_positions(query)
+                    .map(|(doc_id, _)| doc_id)
+                    .sorted()
+                    .dedup()
+                    .collect::<Vec<_>>();
+                assert_eq!(multi_doc_index.docs_containing(query), expected);
+                assert_eq!(multi_doc_index.doc_freq(query), expected.len());
+
+                let mut counts = HashMap::new();
+                for (doc_id, _) in multi_doc_index.doc_positions(query) {
+                    *counts.entry(doc_id).or_insert(0) += 1;
+                }
+                let expected = counts
+                    .into_iter()
+                    .sorted_by_key(|&(doc_id, count)| (Reverse(count), doc_id))
+                    .collect::<Vec<_>>();
+                for &k in &[0, 1, 2, usize::MAX] {
+                    let top_docs = multi_doc_index.top_docs(query, k);
+                    assert_eq!(top_docs, &expected[..k.min(expected.len())]);
+                }
+            }
+        }
+
+        TestResult::passed()
+    }
+}
@@ -0,0 +1,323 @@
+use crate::build::{build_suffix_array, VecWrapper};
+use crate::lcp::common_prefix_len;
+use crate::{Index, Result};
+use std::collections::BTreeMap;
+use std::ops::Range;
+
+const NONE: u32 = u32::MAX;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Factor {
+    pub position: u32,
+    pub len: u32,
+    /// Position of an earlier occurrence of the phrase, or `None` if the
+    /// phrase is a single character not seen before.
+    pub source: Option<u32>,
+}
+
+/// Iterator returned by `Index::lz77_factors`.
+pub struct Lz77Factors<'a> {
+    text: &'a str,
+    psv: Vec<u32>,
+    nsv: Vec<u32>,
+    position: usize,
+}
+
+impl<'a> Iterator for Lz77Factors<'a> {
+    type Item = Factor;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let p = self.position;
+        let c = self.text[p..].chars().next()?;
+
+        // the longest previous match starts at the nearest smaller position
+        // on either side in suffix array order
+        let (len, source) = [self.psv[p], self.nsv[p]]
+            .iter()
+            .filter(|&&q| q != NONE)
+            .map(|&q| {
+                (
+                    common_prefix_len(&self.text[p..], &self.text[q as usize..]),
+                    q,
+                )
+            })
+            .max()
+            .filter(|&(len, _)| len > 0)
+            .map_or((c.len_utf8(), None), |(len, q)| (len, Some(q)));
+
+        self.position += len;
+        Some(Factor {
+            position: p as u32,
+            len: len as u32,
+            source,
+        })
+    }
+}
+
+/// Iterator returned by `Index::lz_end_factors`.
+pub struct LzEndFactors<'a> {
+    text: &'a str,
+    // the text reversed character by character, whose suffixes are the
+    // reversed prefixes of the text
+    reversed: String,
+    reversed_sa: Vec<u32>,
+    // one plus the rank of the suffix of `reversed` at each position, or 0
+    // for the empty suffix at the end
+    reversed_ranks: Vec<u32>,
+    // positions of the phrase ends so far by the ranks of the reversed
+    // prefixes ending there
+    ends: BTreeMap<u32, u32>,
+    position: usize,
+}
+
+impl<'a> Iterator for LzEndFactors<'a> {
+    type Item = Factor;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let p = self.position;
+        let c = self.text[p..].chars().next()?;
+
+        // Extends the phrase one character at a time, keeping the ranks of
+        // the suffixes of `reversed` starting with the reversed phrase, as
+        // long as the phrase occurs at all. Prepending a character to the
+        // reversed phrase narrows the suffixes starting with the character
+        // to those followed by suffixes of the current ranks.
+        let (mut len, mut source) = (c.len_utf8(), None);
+        let mut ranks = 0..self.reversed_sa.len() as u32 + 1;
+        let mut phrase_len = 0;
+        for c in self.text[p..].chars() {
+            let block = self.char_range(c);
+            let next_rank = |&q: &u32| self.reversed_ranks[q as usize + c.len_utf8()];
+            let suffixes = &self.reversed_sa[block.clone()];
+            let begin = suffixes.partition_point(|q| next_rank(q) < ranks.start);
+            let end = suffixes.partition_point(|q| next_rank(q) < ranks.end);
+            if begin == end {
+                break;
+            }
+            ranks = (block.start + begin) as u32 + 1..(block.start + end) as u32 + 1;
+            phrase_len += c.len_utf8();
+
+            // the phrase ends at a phrase end if its reversal is a prefix of
+            // the reversed prefix ending there
+            if let Some((_, &e)) = self.ends.range(ranks.clone()).next() {
+                len = phrase_len;
+                source = Some(e - phrase_len as u32);
+            }
+        }
+
+        self.position += len;
+        let rank = self.reversed_ranks[self.text.len() - self.position];
+        self.ends.insert(rank, self.position as u32);
+        Some(Factor {
+            position: p as u32,
+            len: len as u32,
+            source,
+        })
+    }
+}
+
+impl LzEndFactors<'_> {
+    // Returns the range of the suffixes of `reversed` starting with `c`.
+    fn char_range(&self, c: char) -> Range<usize> {
+        let mut buf = [0; 4];
+        let c = c.encode_utf8(&mut buf).as_bytes();
+        let suffix = |q: u32| &self.reversed.as_bytes()[q as usize..];
+        let begin = self.reversed_sa.partition_point(|&q| suffix(q) < c);
+        let end = self
+            .reversed_sa
+            .partition_point(|&q| suffix(q) < c || suffix(q).starts_with(c));
+        begin..end
+    }
+}
+
+impl<'a, 'b> Index<'a, 'b> {
+    /// Returns an iterator over the phrases of the LZ77 factorization of the
+    /// text, allowing phrases to overlap their sources.
+    ///
+    /// Previous occurrences may start anywhere and run across documents, so
+    /// the index must include all suffixes and must not be generalized.
+    pub fn lz77_factors(&self) -> Result<Lz77Factors<'a>> {
+        if self.is_sparse() || self.is_generalized() {
+            return Err(crate::Error::InvalidOption(
+                "LZ77 factorization needs an index of all suffixes of the whole text".to_string(),
+            ));
+        }
+        let sa = self.suffix_array();
+        let mut psv = vec![NONE; self.text().len()];
+        let mut nsv = vec![NONE; self.text().len()];
+
+        let mut stack: Vec<u32> = Vec::new();
+        for &p in sa {
+            while stack.last().is_some_and(|&q| q > p) {
+                stack.pop();
+            }
+            psv[p as usize] = stack.last().copied().unwrap_or(NONE);
+            stack.push(p);
+        }
+        stack.clear();
+        for &p in sa.iter().rev() {
+            while stack.last().is_some_and(|&q| q > p) {
+                stack.pop();
+            }
+            nsv[p as usize] = stack.last().copied().unwrap_or(NONE);
+            stack.push(p);
+        }
+
+        Ok(Lz77Factors {
+            text: self.text(),
+            psv,
+            nsv,
+            position: 0,
+        })
+    }
+
+    /// Returns an iterator over the phrases of the LZ-End factorization of
+    /// the text, where each phrase is the longest prefix of the rest of the
+    /// text occurring as a suffix of the text up to the end of an earlier
+    /// phrase, or a single character if there is no such prefix.
+    ///
+    /// Unlike LZ77, a phrase can be extracted by copying whole earlier
+    /// phrases. The suffix array of the reversed text is built in memory
+    /// for the factorization, and each phrase takes time proportional to
+    /// the length of the longest earlier occurrence of it times the
+    /// logarithm of the text length.
+    pub fn lz_end_factors(&self) -> LzEndFactors<'a> {
+        let reversed = self.text().chars().rev().collect::<String>();
+        let mut sa = VecWrapper(Vec::new());
+        // the text length has been checked when building this index
+        build_suffix_array(&reversed, u32::MAX, &mut sa).unwrap();
+        let mut ranks = vec![0; reversed.len() + 1];
+        for (rank, &q) in sa.0.iter().enumerate() {
+            ranks[q as usize] = rank as u32 + 1;
+        }
+
+        LzEndFactors {
+            text: self.text(),
+            reversed,
+            reversed_sa: sa.0,
+            reversed_ranks: ranks,
+            ends: BTreeMap::new(),
+            position: 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Factor;
+    use crate::test_util::to_small_alphabet;
+    use crate::{IndexBuilder, MultiDocIndexBuilder, SuffixStarts};
+
+    #[quickcheck]
+    fn lz77_factors(text: String) {
+        let text = to_small_alphabet(&text, "ab_");
+        let index = IndexBuilder::new(&text).build().unwrap();
+
+        let mut p = 0;
+        for factor in index.lz77_factors().unwrap() {
+            assert_eq!(factor.position as usize, p);
+            let len = factor.len as usize;
+            let longest = text[..p]
+                .char_indices()
+                .map(|(q, _)| super::common_prefix_len(&text[p..], &text[q..]))
+                .max()
+                .unwrap_or(0);
+            match factor.source {
+                Some(q) => {
+                    assert_eq!(len, longest);
+                    assert!((q as usize) < p);
+                    assert_eq!(&text[q as usize..q as usize + len], &text[p..p + len]);
+                }
+                None => {
+                    assert_eq!(longest, 0);
+                    assert_eq!(len, text[p..].chars().next().unwrap().len_utf8());
+                }
+            }
+            p += len;
+        }
+        assert_eq!(p, text.len());
+    }
+
+    #[quickcheck]
+    fn lz_end_factors(text: String) {
+        let text = to_small_alphabet(&text, "ab_");
+        let index = IndexBuilder::new(&text).build().unwrap();
+
+        let mut p = 0;
+        let mut ends = Vec::new();
+        for factor in index.lz_end_factors() {
+            assert_eq!(factor.position as usize, p);
+            let len = factor.len as usize;
+            let longest = text[p..]
+                .char_indices()
+                .map(|(l, c)| l + c.len_utf8())
+                .filter(|&l| {
+                    ends.iter()
+                        .any(|&e| e >= l && text.get(e - l..e) == Some(&text[p..p + l]))
+                })
+                .max()
+                .unwrap_or(0);
+            match factor.source {
+                Some(q) => {
+                    assert_eq!(len, longest);
+                    assert!(ends.contains(&(q as usize + len)));
+                    assert_eq!(&text[q as usize..q as usize + len], &text[p..p + len]);
+                }
+                None => {
+                    assert_eq!(longest, 0);
+                    assert_eq!(len, text[p..].chars().next().unwrap().len_utf8());
+                }
+            }
+            p += len;
+            ends.push(p);
+        }
+        assert_eq!(p, text.len());
+    }
+
+    #[test]
+    fn phrases() {
+        let index = IndexBuilder::new("abababb").build().unwrap();
+        let factors = index.lz77_factors().unwrap().collect::<Vec<_>>();
+        assert_eq!(
+            factors,
+            [
+                Factor {
+                    position: 0,
+                    len: 1,
+                    source: None
+                },
+                Factor {
+                    position: 1,
+                    len: 1,
+                    source: None
+                },
+                Factor {
+                    position: 2,
+                    len: 4,
+                    source: Some(0)
+                },
+                Factor {
+                    position: 6,
+                    len: 1,
+                    source: Some(1)
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn incomplete_index() {
+        let text = "ab ab\nab";
+        let index = IndexBuilder::new(text)
+            .suffix_starts(SuffixStarts::WordStarts)
+            .build()
+            .unwrap();
+        assert!(index.lz77_factors().is_err());
+
+        let multi_doc_index = MultiDocIndexBuilder::new(text)
+            .generalized(true)
+            .build()
+            .unwrap();
+        assert!(multi_doc_index.index().lz77_factors().is_err());
+    }
+}
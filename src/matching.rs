@@ -0,0 +1,154 @@
+use crate::rmq::MinTree;
+use crate::Index;
+use std::borrow::Cow;
+use std::ops::Range;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Span {
+    Novel(Range<usize>),
+    Present(Range<usize>),
+}
+
+impl<'a, 'b> Index<'a, 'b> {
+    /// Returns, for each character of `text` in order, the length in bytes of
+    /// the longest substring starting at the character that occurs in the
+    /// indexed text.
+    ///
+    /// On a sparse index, only occurrences starting at indexed positions
+    /// count, and the search restarts at every character, which takes time
+    /// proportional to the sum of the lengths, quadratic in the length of
+    /// `text` in the worst case.
+    pub fn matching_statistics(&self, text: &str) -> Vec<usize> {
+        let sa = self.suffix_array();
+        let full = !self.is_sparse();
+
+        // ranks and lcps let us move from the interval of text[i..i + h] to
+        // that of text[i + c..i + h] without searching from scratch
+        let (rank, lcp) = if full {
+            (self.ranks(), MinTree::new(&self.lcp_array()))
+        } else {
+            (Cow::Borrowed(&[][..]), MinTree::new(&[]))
+        };
+
+        let mut stats = Vec::new();
+        let mut range = 0..sa.len();
+        let mut len = 0;
+        for (i, c) in text.char_indices() {
+            let mut buf = [0; 4];
+            for next in text[i + len..].chars() {
+                let child = self.narrow(range.clone(), len, next.encode_utf8(&mut buf));
+                if child.is_empty() {
+                    break;
+                }
+                range = child;
+                len += next.len_utf8();
+            }
+            stats.push(len);
+
+            if !full || len <= c.len_utf8() {
+                range = 0..sa.len();
+                len = 0;
+                continue;
+            }
+            len -= c.len_utf8();
+            let r = rank[sa[range.start] as usize + c.len_utf8()] as usize;
+            let start = lcp.prev_less(r, len as u32).unwrap_or(0);
+            let end = lcp.next_less(r + 1, len as u32).unwrap_or(sa.len());
+            range = start..end.min(sa.len());
+        }
+
+        stats
+    }
+
+    /// Splits `text` into spans that are covered by substrings of at least
+    /// `min_len` bytes occurring in the indexed text, and spans that are not.
+    pub fn novel_spans(&self, text: &str, min_len: usize) -> Vec<Span> {
+        let mut spans = Vec::new();
+        let mut covered_until = 0;
+        let mut span_start = 0;
+        let mut span_covered = false;
+
+        let stats = self.matching_statistics(text);
+        for ((i, _), len) in text.char_indices().zip(stats) {
+            if len >= min_len.max(1) {
+                covered_until = covered_until.max(i + len);
+            }
+            let covered = i < covered_until;
+            if covered != span_covered && i > span_start {
+                spans.push(make_span(span_start..i, span_covered));
+                span_start = i;
+            }
+            span_covered = covered;
+        }
+        if span_start < text.len() {
+            spans.push(make_span(span_start..text.len(), span_covered));
+        }
+
+        spans
+    }
+}
+
+fn make_span(range: Range<usize>, covered: bool) -> Span {
+    if covered {
+        Span::Present(range)
+    } else {
+        Span::Novel(range)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Span;
+    use crate::test_util::to_small_alphabet;
+    use crate::{IndexBuilder, SuffixStarts};
+
+    #[quickcheck]
+    fn matching_statistics(text: String, query: String, sparse: bool) {
+        let text = to_small_alphabet(&text, "ab _");
+        let query = to_small_alphabet(&query, "ab _");
+        let starts = if sparse {
+            SuffixStarts::AfterWhitespace
+        } else {
+            SuffixStarts::All
+        };
+        let index = IndexBuilder::new(&text)
+            .suffix_starts(starts.clone())
+            .build()
+            .unwrap();
+        let occurs = |s: &str| {
+            (0..text.len())
+                .filter(|&p| text.is_char_boundary(p) && starts.accepts(&text, p))
+                .any(|p| text[p..].starts_with(s))
+        };
+
+        let expected = query
+            .char_indices()
+            .map(|(i, _)| {
+                query[i..]
+                    .char_indices()
+                    .map(|(j, c)| j + c.len_utf8())
+                    .take_while(|&j| occurs(&query[i..i + j]))
+                    .last()
+                    .unwrap_or(0)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(index.matching_statistics(&query), expected);
+    }
+
+    #[test]
+    fn novel_spans() {
+        let index = IndexBuilder::new("the quick brown fox jumps")
+            .build()
+            .unwrap();
+        assert_eq!(
+            index.novel_spans("a quick brown dog jumps", 5),
+            [
+                Span::Novel(0..1),
+                Span::Present(1..14),
+                Span::Novel(14..17),
+                Span::Present(17..23),
+            ]
+        );
+        assert!(index.novel_spans("", 5).is_empty());
+    }
+}
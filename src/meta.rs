@@ -0,0 +1,131 @@
+use crate::Result;
+
+const HAS_NAME: u8 = 1;
+const HAS_ID: u8 = 2;
+
+/// Metadata attached to a document with `MultiDocIndexBuilder::doc_meta`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DocMeta {
+    /// Name or path of the document.
+    pub name: Option<String>,
+    /// Identifier of the document outside the index.
+    pub id: Option<String>,
+    pub fields: Vec<(String, String)>,
+}
+
+impl DocMeta {
+    pub fn with_name<S: ToString>(name: S) -> Self {
+        Self {
+            name: Some(name.to_string()),
+            ..Default::default()
+        }
+    }
+
+    /// Returns the value of the first field with `key`.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.fields
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+// Serializes metadata of each document as a byte of flags followed by
+// NUL-terminated name, id, keys and values, returning the offsets of the
+// records along with the records. The offsets have one more element than
+// `metas` so that the record of document `i` is at `offsets[i]..offsets[i + 1]`.
+pub(crate) fn encode(metas: &[DocMeta]) -> Result<(Vec<u32>, Vec<u8>)> {
+    let mut offsets = vec![0];
+    let mut bytes = Vec::new();
+    for meta in metas {
+        let mut flags = 0;
+        if meta.name.is_some() {
+            flags |= HAS_NAME;
+        }
+        if meta.id.is_some() {
+            flags |= HAS_ID;
+        }
+        bytes.push(flags);
+
+        let strings = meta
+            .name
+            .iter()
+            .chain(&meta.id)
+            .chain(meta.fields.iter().flat_map(|(k, v)| vec![k, v]));
+        for s in strings {
+            if s.contains('\0') {
+                return Err(crate::Error::InvalidOption(
+                    "document metadata cannot contain NUL characters".to_string(),
+                ));
+            }
+            bytes.extend_from_slice(s.as_bytes());
+            bytes.push(0);
+        }
+        offsets.push(bytes.len() as u32);
+    }
+    Ok((offsets, bytes))
+}
+
+// Returns `None` if the record is malformed.
+pub(crate) fn decode(record: &[u8]) -> Option<DocMeta> {
+    let (&flags, rest) = record.split_first()?;
+    let rest = std::str::from_utf8(rest).ok()?;
+    let mut strings = match rest.strip_suffix('\0') {
+        Some(rest) => rest.split('\0').map(str::to_string).collect::<Vec<_>>(),
+        None if rest.is_empty() => Vec::new(),
+        None => return None,
+    }
+    .into_iter();
+
+    let mut meta = DocMeta::default();
+    if flags & HAS_NAME != 0 {
+        meta.name = Some(strings.next()?);
+    }
+    if flags & HAS_ID != 0 {
+        meta.id = Some(strings.next()?);
+    }
+    while let Some(key) = strings.next() {
+        meta.fields.push((key, strings.next()?));
+    }
+    Some(meta)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode, encode, DocMeta};
+
+    #[quickcheck]
+    fn roundtrip(metas: Vec<(Option<String>, Option<String>, Vec<String>)>) {
+        let metas = metas
+            .into_iter()
+            .map(|(name, id, fields)| DocMeta {
+                name,
+                id,
+                fields: fields
+                    .chunks_exact(2)
+                    .map(|kv| (kv[0].clone(), kv[1].clone()))
+                    .collect(),
+            })
+            .collect::<Vec<_>>();
+        let has_nul = metas.iter().any(|meta| {
+            meta.name
+                .iter()
+                .chain(&meta.id)
+                .chain(meta.fields.iter().flat_map(|(k, v)| vec![k, v]))
+                .any(|s| s.contains('\0'))
+        });
+
+        match encode(&metas) {
+            Ok((offsets, bytes)) => {
+                assert!(!has_nul);
+                assert_eq!(offsets.len(), metas.len() + 1);
+                assert_eq!(*offsets.last().unwrap() as usize, bytes.len());
+                for (i, meta) in metas.iter().enumerate() {
+                    let record = &bytes[offsets[i] as usize..offsets[i + 1] as usize];
+                    assert_eq!(decode(record).as_ref(), Some(meta));
+                }
+            }
+            Err(_) => assert!(has_nul),
+        }
+    }
+}
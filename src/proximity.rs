@@ -0,0 +1,189 @@
+use crate::MultiDocIndex;
+
+impl<'a, 'b> MultiDocIndex<'a, 'b> {
+    /// Returns `(doc_id, position in document, length)` of minimal spans of
+    /// at most `max_len` bytes containing an occurrence of every pattern in
+    /// any order. For example, `a` within 50 bytes of `b` is
+    /// `near(&[a, b], a.len() + 50 + b.len())`.
+    pub fn near(&self, patterns: &[&str], max_len: u32) -> Vec<(u32, u32, u32)> {
+        self.spans(patterns, max_len, |positions, lens, start| {
+            // the earliest occurrence of each pattern from `start` ends first
+            let mut end = start;
+            for (positions, &len) in positions.iter().zip(lens) {
+                let i = positions.partition_point(|&p| p < start);
+                end = end.max(positions.get(i)? + len);
+            }
+            Some(end)
+        })
+    }
+
+    /// Returns `(doc_id, position in document, length)` of minimal spans of
+    /// at most `max_len` bytes in which the patterns occur in order without
+    /// overlapping.
+    pub fn sequence(&self, patterns: &[&str], max_len: u32) -> Vec<(u32, u32, u32)> {
+        self.spans(patterns, max_len, |positions, lens, start| {
+            if positions[0].binary_search(&start).is_err() {
+                return None;
+            }
+            let mut end = start + lens[0];
+            for (positions, &len) in positions.iter().zip(lens).skip(1) {
+                let i = positions.partition_point(|&p| p < end);
+                end = positions.get(i)? + len;
+            }
+            Some(end)
+        })
+    }
+
+    // Collects minimal spans from the shortest span starting at each
+    // occurrence, given by `span_end(positions in document, lengths, start)`
+    // which is non-decreasing in `start`.
+    fn spans<F>(&self, patterns: &[&str], max_len: u32, span_end: F) -> Vec<(u32, u32, u32)>
+    where
+        F: Fn(&[&[u32]], &[u32], u32) -> Option<u32>,
+    {
+        if patterns.is_empty() {
+            return Vec::new();
+        }
+
+        let hits = patterns
+            .iter()
+            .map(|pattern| {
+                let mut positions = self.doc_positions(pattern).collect::<Vec<_>>();
+                positions.sort_unstable();
+                positions
+            })
+            .collect::<Vec<_>>();
+        let lens = patterns.iter().map(|p| p.len() as u32).collect::<Vec<_>>();
+
+        let mut doc_ids = hits[0]
+            .iter()
+            .map(|&(doc_id, _)| doc_id)
+            .collect::<Vec<_>>();
+        doc_ids.dedup();
+        for hits in &hits[1..] {
+            doc_ids.retain(|&doc_id| {
+                let i = hits.partition_point(|&(d, _)| d < doc_id);
+                hits.get(i).is_some_and(|&(d, _)| d == doc_id)
+            });
+        }
+
+        let mut spans = Vec::new();
+        for doc_id in doc_ids {
+            let doc_hits = hits
+                .iter()
+                .map(|hits| {
+                    let begin = hits.partition_point(|&(d, _)| d < doc_id);
+                    let end = hits.partition_point(|&(d, _)| d <= doc_id);
+                    hits[begin..end].iter().map(|&(_, p)| p).collect::<Vec<_>>()
+                })
+                .collect::<Vec<_>>();
+            let positions = doc_hits.iter().map(Vec::as_slice).collect::<Vec<_>>();
+
+            let mut starts = doc_hits.concat();
+            starts.sort_unstable();
+            starts.dedup();
+            let doc_spans = starts
+                .into_iter()
+                .filter_map(|start| span_end(&positions, &lens, start).map(|end| (start, end)))
+                .collect::<Vec<_>>();
+
+            // a span is minimal unless a later start has the same end
+            for (i, &(start, end)) in doc_spans.iter().enumerate() {
+                let minimal = doc_spans.get(i + 1).is_none_or(|&(_, next)| next != end);
+                if minimal && end - start <= max_len {
+                    spans.push((doc_id, start, end - start));
+                }
+            }
+        }
+        spans
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::to_small_alphabet;
+    use crate::{concat_docs, MultiDocIndexBuilder};
+    use quickcheck::TestResult;
+
+    fn positions_naive(text: &str, pattern: &str) -> Vec<u32> {
+        (0..text.len())
+            .filter(|&i| text[i..].starts_with(pattern))
+            .map(|i| i as u32)
+            .collect()
+    }
+
+    // Returns whether `doc[start..end]` contains the patterns, in order if
+    // `ordered`.
+    fn contains_naive(doc: &str, patterns: &[&str], ordered: bool, start: u32, end: u32) -> bool {
+        let mut cursor = start;
+        patterns.iter().all(|pattern| {
+            let from = if ordered { cursor } else { start };
+            match positions_naive(doc, pattern)
+                .into_iter()
+                .find(|&p| p >= from && p + pattern.len() as u32 <= end)
+            {
+                Some(p) => {
+                    cursor = p + pattern.len() as u32;
+                    true
+                }
+                None => false,
+            }
+        })
+    }
+
+    #[quickcheck]
+    fn spans(texts: Vec<String>, max_len: u32) -> TestResult {
+        let texts = texts
+            .iter()
+            .map(|text| to_small_alphabet(text, "ab"))
+            .collect::<Vec<_>>();
+        let (text, offsets) = concat_docs(&texts);
+        if text.len() > 60 {
+            return TestResult::discard();
+        }
+        let multi_doc_index = MultiDocIndexBuilder::new(&text)
+            .doc_offsets(offsets)
+            .build()
+            .unwrap();
+        let max_len = match max_len % 8 {
+            0 => u32::MAX,
+            x => x,
+        };
+
+        const PATTERNS: &[&[&str]] = &[
+            &["a"],
+            &["a", "b"],
+            &["ab", "b"],
+            &["b", "a", "b"],
+            &["aa", "ba"],
+            &["bb", "bb"],
+        ];
+        for &patterns in PATTERNS {
+            for &ordered in &[false, true] {
+                let mut expected = Vec::new();
+                for (doc_id, doc) in texts.iter().enumerate() {
+                    let len = doc.len() as u32;
+                    for start in 0..len {
+                        for end in start + 1..=len {
+                            if end - start <= max_len
+                                && contains_naive(doc, patterns, ordered, start, end)
+                                && !contains_naive(doc, patterns, ordered, start + 1, end)
+                                && !contains_naive(doc, patterns, ordered, start, end - 1)
+                            {
+                                expected.push((doc_id as u32, start, end - start));
+                            }
+                        }
+                    }
+                }
+                let spans = if ordered {
+                    multi_doc_index.sequence(patterns, max_len)
+                } else {
+                    multi_doc_index.near(patterns, max_len)
+                };
+                assert_eq!(spans, expected, "{:?} {}", patterns, ordered);
+            }
+        }
+
+        TestResult::passed()
+    }
+}
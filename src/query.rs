@@ -0,0 +1,346 @@
+use crate::{MultiDocIndex, Result};
+use itertools::Itertools;
+use std::iter::Peekable;
+use std::str::CharIndices;
+
+// upper bound of the nesting of parentheses and NOT, as the parser and the
+// evaluation recurse into nested queries
+const MAX_DEPTH: usize = 256;
+
+/// Boolean query over documents.
+///
+/// Terms are words separated by whitespace or non-empty phrases in double
+/// quotes, in which `\"` and `\\` are escapes. They are combined with `NOT`,
+/// `AND` and `OR` in decreasing order of precedence, and grouped with
+/// parentheses. Adjacent terms are implicitly joined by `AND`. Parentheses
+/// and `NOT` can be nested up to 256 levels deep.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Query {
+    Term(String),
+    And(Vec<Query>),
+    Or(Vec<Query>),
+    Not(Box<Query>),
+}
+
+impl Query {
+    pub fn parse(s: &str) -> Result<Query> {
+        let tokens = tokenize(s)?;
+        let mut parser = Parser {
+            tokens,
+            pos: 0,
+            depth: 0,
+        };
+        let query = parser.or()?;
+        match parser.tokens.get(parser.pos) {
+            None => Ok(query),
+            Some(token) => Err(invalid_query(format!("unexpected {}", token))),
+        }
+    }
+}
+
+impl<'a, 'b> MultiDocIndex<'a, 'b> {
+    /// Returns the IDs of documents matching `query` in ascending order.
+    pub fn docs_matching(&self, query: &Query) -> Vec<u32> {
+        match query {
+            Query::Term(term) => self.docs_containing(term),
+            Query::And(queries) => {
+                let (first, rest) = match queries.split_first() {
+                    Some(split) => split,
+                    None => return (0..self.num_docs() as u32).collect(),
+                };
+                let mut doc_ids = self.docs_matching(first);
+                for query in rest {
+                    if doc_ids.is_empty() {
+                        break;
+                    }
+                    doc_ids = intersect(&doc_ids, &self.docs_matching(query));
+                }
+                doc_ids
+            }
+            Query::Or(queries) => queries
+                .iter()
+                .fold(Vec::new(), |acc, q| union(&acc, &self.docs_matching(q))),
+            Query::Not(query) => {
+                let excluded = self.docs_matching(query);
+                let mut excluded = excluded.iter().peekable();
+                (0..self.num_docs() as u32)
+                    .filter(|&doc_id| excluded.next_if_eq(&&doc_id).is_none())
+                    .collect()
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Token {
+    Term(String),
+    And,
+    Or,
+    Not,
+    Open,
+    Close,
+}
+
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::Term(term) => write!(f, "{:?}", term),
+            Token::And => f.write_str("AND"),
+            Token::Or => f.write_str("OR"),
+            Token::Not => f.write_str("NOT"),
+            Token::Open => f.write_str("'('"),
+            Token::Close => f.write_str("')'"),
+        }
+    }
+}
+
+fn tokenize(s: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = s.char_indices().peekable();
+    while let Some(&(i, c)) = chars.peek() {
+        match c {
+            _ if c.is_whitespace() => {
+                chars.next();
+            }
+            '(' => {
+                chars.next();
+                tokens.push(Token::Open);
+            }
+            ')' => {
+                chars.next();
+                tokens.push(Token::Close);
+            }
+            '"' => {
+                chars.next();
+                tokens.push(Token::Term(phrase(&mut chars)?));
+            }
+            _ => {
+                let mut end = s.len();
+                while let Some(&(j, c)) = chars.peek() {
+                    if c.is_whitespace() || c == '(' || c == ')' || c == '"' {
+                        end = j;
+                        break;
+                    }
+                    chars.next();
+                }
+                tokens.push(match &s[i..end] {
+                    "AND" => Token::And,
+                    "OR" => Token::Or,
+                    "NOT" => Token::Not,
+                    word => Token::Term(word.to_string()),
+                });
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+// Reads a phrase after the opening quote.
+fn phrase(chars: &mut Peekable<CharIndices>) -> Result<String> {
+    let mut phrase = String::new();
+    while let Some((_, c)) = chars.next() {
+        match c {
+            '"' if phrase.is_empty() => {
+                return Err(invalid_query("empty phrase".to_string()));
+            }
+            '"' => return Ok(phrase),
+            '\\' => match chars.next() {
+                Some((_, c)) if c == '"' || c == '\\' => phrase.push(c),
+                _ => return Err(invalid_query("invalid escape in phrase".to_string())),
+            },
+            c => phrase.push(c),
+        }
+    }
+    Err(invalid_query("unterminated phrase".to_string()))
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    depth: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn or(&mut self) -> Result<Query> {
+        let mut queries = vec![self.and()?];
+        while self.peek() == Some(&Token::Or) {
+            self.pos += 1;
+            queries.push(self.and()?);
+        }
+        Ok(flatten(queries, Query::Or))
+    }
+
+    fn and(&mut self) -> Result<Query> {
+        let mut queries = vec![self.not()?];
+        loop {
+            match self.peek() {
+                Some(Token::And) => self.pos += 1,
+                Some(Token::Term(_)) | Some(Token::Not) | Some(Token::Open) => {}
+                _ => break,
+            }
+            queries.push(self.not()?);
+        }
+        Ok(flatten(queries, Query::And))
+    }
+
+    fn not(&mut self) -> Result<Query> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        match token {
+            Some(Token::Not) => Ok(Query::Not(Box::new(self.nested(Self::not)?))),
+            Some(Token::Term(term)) => Ok(Query::Term(term)),
+            Some(Token::Open) => {
+                let query = self.nested(Self::or)?;
+                match self.peek() {
+                    Some(Token::Close) => {
+                        self.pos += 1;
+                        Ok(query)
+                    }
+                    _ => Err(invalid_query("unclosed parenthesis".to_string())),
+                }
+            }
+            Some(token) => Err(invalid_query(format!("unexpected {}", token))),
+            None => Err(invalid_query("unexpected end of query".to_string())),
+        }
+    }
+
+    fn nested(&mut self, parse: fn(&mut Self) -> Result<Query>) -> Result<Query> {
+        if self.depth == MAX_DEPTH {
+            return Err(invalid_query(format!(
+                "query nested more than {} levels deep",
+                MAX_DEPTH
+            )));
+        }
+        self.depth += 1;
+        let query = parse(self);
+        self.depth -= 1;
+        query
+    }
+}
+
+fn flatten(mut queries: Vec<Query>, f: fn(Vec<Query>) -> Query) -> Query {
+    if queries.len() == 1 {
+        queries.pop().unwrap()
+    } else {
+        f(queries)
+    }
+}
+
+fn intersect(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut b = b.iter().peekable();
+    a.iter()
+        .copied()
+        .filter(|x| {
+            while b.next_if(|y| *y < x).is_some() {}
+            b.peek() == Some(&x)
+        })
+        .collect()
+}
+
+fn union(a: &[u32], b: &[u32]) -> Vec<u32> {
+    itertools::merge(a, b).copied().dedup().collect()
+}
+
+fn invalid_query(message: String) -> crate::Error {
+    crate::Error::InvalidPattern(message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Query;
+    use crate::test_util::to_small_alphabet;
+    use crate::{concat_docs, MultiDocIndexBuilder};
+    use quickcheck::TestResult;
+
+    fn term(s: &str) -> Query {
+        Query::Term(s.to_string())
+    }
+
+    fn matches_naive(query: &Query, doc: &str) -> bool {
+        match query {
+            Query::Term(term) => doc.contains(term.as_str()),
+            Query::And(queries) => queries.iter().all(|q| matches_naive(q, doc)),
+            Query::Or(queries) => queries.iter().any(|q| matches_naive(q, doc)),
+            Query::Not(query) => !matches_naive(query, doc),
+        }
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!(
+            Query::parse("a b OR NOT c AND (d OR e)").unwrap(),
+            Query::Or(vec![
+                Query::And(vec![term("a"), term("b")]),
+                Query::And(vec![
+                    Query::Not(Box::new(term("c"))),
+                    Query::Or(vec![term("d"), term("e")]),
+                ]),
+            ])
+        );
+        assert_eq!(
+            Query::parse(r#" "a (b) OR" NOT"\"c\\" "#).unwrap(),
+            Query::And(vec![term("a (b) OR"), Query::Not(Box::new(term("\"c\\"))),])
+        );
+        assert_eq!(
+            Query::parse("and or not").unwrap(),
+            Query::And(vec![term("and"), term("or"), term("not")])
+        );
+
+        for s in &[
+            "", "a AND", "OR a", "(a", "a)", "NOT", "\"a", r#""\a""#, "()", "\"\"", "a \"\" b",
+        ] {
+            assert!(Query::parse(s).is_err(), "{}", s);
+        }
+        assert!(matches!(
+            Query::parse(r#"a "" b"#),
+            Err(crate::Error::InvalidPattern(message)) if message == "empty phrase"
+        ));
+
+        let nested = |n| format!("{}a{}", "(NOT ".repeat(n), ")".repeat(n));
+        assert!(Query::parse(&nested(super::MAX_DEPTH / 2)).is_ok());
+        assert!(Query::parse(&nested(100_000)).is_err());
+    }
+
+    #[quickcheck]
+    fn docs_matching(texts: Vec<String>, generalized: bool) -> TestResult {
+        let texts = texts
+            .iter()
+            .map(|text| to_small_alphabet(text, "abc"))
+            .collect::<Vec<_>>();
+        if texts.is_empty() {
+            return TestResult::discard();
+        }
+        let (text, offsets) = concat_docs(&texts);
+        let multi_doc_index = MultiDocIndexBuilder::new(&text)
+            .doc_offsets(offsets)
+            .generalized(generalized)
+            .build()
+            .unwrap();
+
+        const QUERIES: &[&str] = &[
+            "a",
+            "ab AND c",
+            "ab c OR ba",
+            "NOT b",
+            "NOT (a OR c)",
+            "\"ab c\" OR cc NOT NOT a",
+            "(ab OR ca) AND NOT (bb c)",
+        ];
+        for s in QUERIES {
+            let query = Query::parse(s).unwrap();
+            let expected = texts
+                .iter()
+                .enumerate()
+                .filter(|(_, doc)| matches_naive(&query, doc))
+                .map(|(i, _)| i as u32)
+                .collect::<Vec<_>>();
+            assert_eq!(multi_doc_index.docs_matching(&query), expected, "{}", s);
+        }
+
+        TestResult::passed()
+    }
+}
@@ -0,0 +1,113 @@
+use crate::MultiDocIndex;
+use std::collections::HashMap;
+
+// BM25 parameters commonly used as defaults
+const K1: f64 = 1.2;
+const B: f64 = 0.75;
+
+impl<'a, 'b> MultiDocIndex<'a, 'b> {
+    /// Returns up to `k` pairs of `(doc_id, score)` of documents containing
+    /// any of `terms`, in descending order of their BM25 scores. Documents
+    /// with equal scores are ordered by ID.
+    pub fn rank(&self, terms: &[&str], k: usize) -> Vec<(u32, f64)> {
+        let num_docs = self.num_docs() as f64;
+        let doc_len = |doc_id: u32| self.doc(doc_id).unwrap().len() as f64;
+        // documents start at 0 and are separated by the delimiter, so they
+        // take up all of the text but the delimiters between them
+        let delims_len = self.delimiter().len() * self.num_docs().saturating_sub(1);
+        let avg_doc_len = (self.index().text().len() - delims_len) as f64 / num_docs;
+
+        let mut scores = HashMap::new();
+        for term in terms {
+            let mut tfs = HashMap::new();
+            for (doc_id, _) in self.doc_positions(term) {
+                *tfs.entry(doc_id).or_insert(0) += 1;
+            }
+            let df = tfs.len() as f64;
+            let idf = ((num_docs - df + 0.5) / (df + 0.5) + 1.0).ln();
+            for (doc_id, tf) in tfs {
+                let tf = tf as f64;
+                let norm = 1.0 - B + B * doc_len(doc_id) / avg_doc_len.max(1.0);
+                *scores.entry(doc_id).or_insert(0.0) += idf * tf * (K1 + 1.0) / (tf + K1 * norm);
+            }
+        }
+
+        let mut scores = scores.into_iter().collect::<Vec<_>>();
+        scores.sort_unstable_by(|(a_id, a), (b_id, b)| b.total_cmp(a).then(a_id.cmp(b_id)));
+        scores.truncate(k);
+        scores
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{B, K1};
+    use crate::test_util::to_small_alphabet;
+    use crate::{concat_docs, MultiDocIndexBuilder};
+    use quickcheck::TestResult;
+
+    fn freq_naive(text: &str, pattern: &str) -> usize {
+        (0..text.len())
+            .filter(|&i| text[i..].starts_with(pattern))
+            .count()
+    }
+
+    #[quickcheck]
+    fn rank(texts: Vec<String>, k: usize) -> TestResult {
+        let texts = texts
+            .iter()
+            .map(|text| to_small_alphabet(text, "abc"))
+            .collect::<Vec<_>>();
+        if texts.is_empty() {
+            return TestResult::discard();
+        }
+        let (text, offsets) = concat_docs(&texts);
+        let multi_doc_index = MultiDocIndexBuilder::new(&text)
+            .doc_offsets(offsets)
+            .build()
+            .unwrap();
+        let k = k % 8;
+
+        let n = texts.len() as f64;
+        let avg_len = texts.iter().map(|t| t.len() as f64).sum::<f64>() / n;
+        for terms in &[&["a"][..], &["ab", "c"], &["b", "b", "cab"]] {
+            let expected = texts
+                .iter()
+                .map(|doc| {
+                    terms
+                        .iter()
+                        .map(|term| {
+                            let tf = freq_naive(doc, term) as f64;
+                            let df = texts.iter().filter(|t| t.contains(term)).count() as f64;
+                            let idf = ((n - df + 0.5) / (df + 0.5) + 1.0).ln();
+                            let norm = 1.0 - B + B * doc.len() as f64 / avg_len.max(1.0);
+                            idf * tf * (K1 + 1.0) / (tf + K1 * norm)
+                        })
+                        .sum::<f64>()
+                })
+                .collect::<Vec<_>>();
+            let num_matching = texts
+                .iter()
+                .filter(|doc| terms.iter().any(|term| doc.contains(term)))
+                .count();
+
+            let ranked = multi_doc_index.rank(terms, k);
+            assert_eq!(ranked.len(), k.min(num_matching));
+            for (i, &(doc_id, score)) in ranked.iter().enumerate() {
+                assert!((score - expected[doc_id as usize]).abs() < 1e-9);
+                if i > 0 {
+                    assert!(ranked[i - 1].1 >= score);
+                }
+            }
+            if let Some(&(_, min_score)) = ranked.last() {
+                for (doc_id, &score) in expected.iter().enumerate() {
+                    if !ranked.iter().any(|&(id, _)| id == doc_id as u32) {
+                        assert!(score <= min_score + 1e-9);
+                    }
+                }
+            }
+        }
+
+        TestResult::passed()
+    }
+}
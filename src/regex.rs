@@ -0,0 +1,288 @@
+use crate::{Index, MultiDocIndex, Result};
+use regex_automata::meta::Regex;
+use regex_automata::{Anchored, Input};
+use regex_syntax::hir::literal::{ExtractKind, Extractor};
+use regex_syntax::hir::{Hir, HirKind};
+
+impl<'a, 'b> Index<'a, 'b> {
+    /// Returns `(position, length)` of the match starting at each position
+    /// where `pattern` matches, sorted by position. On a sparse index, only
+    /// matches starting at indexed positions are returned.
+    ///
+    /// Candidate positions are found through literals every match starts
+    /// with, or on an index of all suffixes of the whole text, literals
+    /// every match contains. Patterns without such literals, or with
+    /// contained literals and matches of unbounded length (e.g. `\w+foo`),
+    /// fall back to scanning the whole text.
+    pub fn regex_positions(&self, pattern: &str) -> Result<Vec<(u32, u32)>> {
+        let hir = regex_syntax::parse(pattern).map_err(|e| invalid_pattern(&e))?;
+        let regex = Regex::builder()
+            .build_from_hir(&hir)
+            .map_err(|e| invalid_pattern(&e))?;
+        let text = self.text();
+        let full = !self.is_sparse() && !self.is_generalized();
+
+        let match_at = |pos: usize| {
+            let input = Input::new(text).range(pos..).anchored(Anchored::Yes);
+            regex
+                .search(&input)
+                .filter(|m| !m.is_empty())
+                .map(|m| (m.start() as u32, m.len() as u32))
+        };
+        let occurrences = |literals: &[String]| {
+            let mut positions = literals
+                .iter()
+                .flat_map(|lit| self.positions(lit).iter().map(move |&q| (q, lit.len())))
+                .collect::<Vec<_>>();
+            positions.sort_unstable();
+            positions
+        };
+
+        let matches = match required_literals(&hir) {
+            Some(Required::Prefixes(prefixes)) => {
+                let mut candidates = occurrences(&prefixes)
+                    .into_iter()
+                    .map(|(q, _)| q)
+                    .collect::<Vec<_>>();
+                candidates.dedup();
+                candidates
+                    .into_iter()
+                    .filter_map(|pos| match_at(pos as usize))
+                    .collect()
+            }
+            Some(Required::Contained(literals)) if full => {
+                let occurrences = occurrences(&literals);
+                match hir.properties().maximum_len() {
+                    _ if occurrences.is_empty() => Vec::new(),
+                    // a match containing an occurrence ends after it and
+                    // starts at most the maximum length before its end
+                    Some(max_len) => {
+                        let mut matches = Vec::new();
+                        let mut pos = 0;
+                        for (q, len) in occurrences {
+                            let q = q as usize;
+                            pos = pos.max((q + len).saturating_sub(max_len));
+                            while pos <= q {
+                                if text.is_char_boundary(pos) {
+                                    matches.extend(match_at(pos));
+                                }
+                                pos += 1;
+                            }
+                        }
+                        matches
+                    }
+                    None => self.scan(&regex),
+                }
+            }
+            _ => self.scan(&regex),
+        };
+
+        Ok(matches)
+    }
+
+    // Finds matches by searching the whole text, keeping those that start at
+    // indexed positions.
+    fn scan(&self, regex: &Regex) -> Vec<(u32, u32)> {
+        let text = self.text();
+        let sorted = if self.is_sparse() {
+            let mut positions = self.suffix_array().to_vec();
+            positions.sort_unstable();
+            Some(positions)
+        } else {
+            None
+        };
+        let mut indexed = sorted.as_ref().map(|positions| positions.iter().peekable());
+
+        let mut matches = Vec::new();
+        let mut pos = 0;
+        while pos < text.len() {
+            let m = match regex.search(&Input::new(text).range(pos..)) {
+                Some(m) => m,
+                None => break,
+            };
+            let start = m.start() as u32;
+            let is_indexed = match &mut indexed {
+                Some(positions) => {
+                    while positions.next_if(|&&p| p < start).is_some() {}
+                    positions.peek() == Some(&&start)
+                }
+                None => true,
+            };
+            if !m.is_empty() && is_indexed {
+                matches.push((start, m.len() as u32));
+            }
+            pos = m.start() + 1;
+            while !text.is_char_boundary(pos) {
+                pos += 1;
+            }
+        }
+        matches
+    }
+}
+
+impl<'a, 'b> MultiDocIndex<'a, 'b> {
+    /// Returns `(doc_id, position in document, length)` of matches.
+    pub fn regex_doc_positions(&self, pattern: &str) -> Result<Vec<(u32, u32, u32)>> {
+        let matches = self
+            .index()
+            .regex_positions(pattern)?
+            .into_iter()
+            .filter_map(|(pos, len)| {
+                self.locate(pos, len)
+                    .map(|(doc_id, pos_in_doc)| (doc_id, pos_in_doc, len))
+            })
+            .collect();
+        Ok(matches)
+    }
+}
+
+fn invalid_pattern<E: ToString>(e: &E) -> crate::Error {
+    crate::Error::InvalidPattern(e.to_string())
+}
+
+// Literals that every match of a regex starts with or contains.
+enum Required {
+    Prefixes(Vec<String>),
+    Contained(Vec<String>),
+}
+
+// Returns a set of non-empty strings such that every match of the regex
+// starts with one of them, or else a set such that every match contains one
+// of them, or None if there is no such finite set.
+fn required_literals(hir: &Hir) -> Option<Required> {
+    if let Some(prefixes) = literals(hir, ExtractKind::Prefix) {
+        return Some(Required::Prefixes(prefixes));
+    }
+
+    // every match contains a match of each suffix of a concatenation, which
+    // starts with one of its prefixes
+    let mut candidates = vec![literals(hir, ExtractKind::Suffix)];
+    if let HirKind::Concat(subs) = hir.kind() {
+        for i in 1..subs.len() {
+            let rest = Hir::concat(subs[i..].to_vec());
+            candidates.push(literals(&rest, ExtractKind::Prefix));
+        }
+    }
+    // the longer the shortest literal, the fewer occurrences to check
+    candidates
+        .into_iter()
+        .flatten()
+        .max_by_key(|literals| literals.iter().map(String::len).min())
+        .map(Required::Contained)
+}
+
+fn literals(hir: &Hir, kind: ExtractKind) -> Option<Vec<String>> {
+    let seq = Extractor::new().kind(kind.clone()).extract(hir);
+    let literals = seq.literals()?;
+    if literals.is_empty() {
+        return None;
+    }
+
+    let mut strings = Vec::with_capacity(literals.len());
+    for lit in literals {
+        // extracted literals may be cut in the middle of a character
+        let bytes = lit.as_bytes();
+        let s = match kind {
+            ExtractKind::Suffix => {
+                let begin = bytes
+                    .iter()
+                    .position(|&b| b & 0xC0 != 0x80)
+                    .unwrap_or(bytes.len());
+                std::str::from_utf8(&bytes[begin..]).ok()?
+            }
+            _ => match std::str::from_utf8(bytes) {
+                Ok(s) => s,
+                Err(e) => std::str::from_utf8(&bytes[..e.valid_up_to()]).unwrap(),
+            },
+        };
+        if s.is_empty() {
+            return None;
+        }
+        strings.push(s.to_string());
+    }
+
+    Some(strings)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::to_small_alphabet;
+    use crate::{IndexBuilder, MultiDocIndexBuilder, SuffixStarts};
+    use itertools::Itertools;
+    use regex_automata::meta::Regex;
+    use regex_automata::{Anchored, Input};
+
+    const PATTERNS: &[&str] = &[
+        "a",
+        "ab+",
+        "(?i)b[ac]",
+        "a|bc|c.a",
+        "[a-c]{2}",
+        r"\bab",
+        ".*c",
+        "a*?b",
+        "x?",
+        ".{0,2}bc",
+        "[^b]{1,3}(?:ab|cc).",
+        r"[^a]\w*ca",
+    ];
+
+    fn regex_positions_naive(text: &str, pattern: &str) -> Vec<(u32, u32)> {
+        let regex = Regex::new(pattern).unwrap();
+        (0..text.len())
+            .filter(|&i| text.is_char_boundary(i))
+            .filter_map(|i| {
+                let input = Input::new(text).range(i..).anchored(Anchored::Yes);
+                regex.search(&input)
+            })
+            .filter(|m| !m.is_empty())
+            .map(|m| (m.start() as u32, m.len() as u32))
+            .collect()
+    }
+
+    #[quickcheck]
+    fn regex_positions(text: String, sparse: bool) {
+        let text = to_small_alphabet(&text, "abc_");
+        let starts = if sparse {
+            SuffixStarts::WordStarts
+        } else {
+            SuffixStarts::All
+        };
+        let index = IndexBuilder::new(&text)
+            .suffix_starts(starts.clone())
+            .build()
+            .unwrap();
+        for pattern in PATTERNS {
+            let expected = regex_positions_naive(&text, pattern)
+                .into_iter()
+                .filter(|&(pos, _)| starts.accepts(&text, pos as usize))
+                .collect::<Vec<_>>();
+            assert_eq!(
+                index.regex_positions(pattern).unwrap(),
+                expected,
+                "{}",
+                pattern
+            );
+        }
+    }
+
+    #[test]
+    fn invalid_pattern() {
+        let index = IndexBuilder::new("abc").build().unwrap();
+        assert!(index.regex_positions("(a").is_err());
+    }
+
+    #[test]
+    fn regex_doc_positions() {
+        let text = ["foo bar", "baz", "bar foo"].iter().join("\n");
+        let multi_doc_index = MultiDocIndexBuilder::new(&text).build().unwrap();
+        assert_eq!(
+            multi_doc_index.regex_doc_positions("ba[rz]").unwrap(),
+            [(0, 4, 3), (1, 0, 3), (2, 0, 3)]
+        );
+        assert!(multi_doc_index
+            .regex_doc_positions(r"r\s+b")
+            .unwrap()
+            .is_empty());
+    }
+}
@@ -0,0 +1,261 @@
+use crate::lcp::for_each_lcp_interval;
+use crate::Index;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Repeat<'a, 'i> {
+    pub substring: &'a str,
+    /// Positions of the occurrences in suffix array order.
+    pub positions: &'i [u32],
+}
+
+const NO_CHAR: u32 = u32::MAX;
+
+impl<'a, 'b> Index<'a, 'b> {
+    pub fn longest_repeated_substring(&self) -> Option<Repeat<'a, '_>> {
+        let lcp = self.lcp_array();
+        let len = *lcp.iter().max()?;
+        if len == 0 {
+            return None;
+        }
+        let i = lcp.iter().position(|&x| x == len).unwrap();
+        let end = i + lcp[i..].iter().take_while(|&&x| x == len).count();
+        Some(self.repeat(len, i - 1, end - 1))
+    }
+
+    /// Returns repeats that cannot be extended to either side without losing
+    /// an occurrence.
+    pub fn maximal_repeats(&self, min_len: usize, min_freq: usize) -> Vec<Repeat<'a, '_>> {
+        let left = self.left_chars();
+
+        // counts of positions at the start of the text and of changes of the
+        // preceding character, to tell left-maximality of an interval in O(1)
+        let mut starts = vec![0; left.len() + 1];
+        let mut changes = vec![0; left.len() + 1];
+        for (i, &c) in left.iter().enumerate() {
+            starts[i + 1] = starts[i] + (c == NO_CHAR) as usize;
+            changes[i + 1] = changes[i] + (i > 0 && c != left[i - 1]) as usize;
+        }
+
+        self.repeats(min_len, min_freq, |lb, rb, _| {
+            starts[rb + 1] > starts[lb] || changes[rb + 1] > changes[lb + 1]
+        })
+    }
+
+    /// Returns maximal repeats that do not occur in any other repeat.
+    pub fn supermaximal_repeats(&self, min_len: usize, min_freq: usize) -> Vec<Repeat<'a, '_>> {
+        let left = self.left_chars();
+        self.repeats(min_len, min_freq, |lb, rb, has_child| {
+            if has_child {
+                return false;
+            }
+            let mut chars = left[lb..=rb].to_vec();
+            chars.sort_unstable();
+            // starts of the text or of documents are distinct from each other
+            chars.windows(2).all(|w| w[0] != w[1] || w[0] == NO_CHAR)
+        })
+    }
+
+    fn repeats<F>(&self, min_len: usize, min_freq: usize, mut pred: F) -> Vec<Repeat<'a, '_>>
+    where
+        F: FnMut(usize, usize, bool) -> bool,
+    {
+        let min_len = min_len.max(1);
+        let min_freq = min_freq.max(2);
+
+        let mut repeats = Vec::new();
+        for_each_lcp_interval(&self.lcp_array(), |lcp, lb, rb, has_child| {
+            if lcp as usize >= min_len && rb - lb + 1 >= min_freq && pred(lb, rb, has_child) {
+                repeats.push(self.repeat(lcp, lb, rb));
+            }
+        });
+        repeats
+    }
+
+    fn repeat(&self, len: u32, lb: usize, rb: usize) -> Repeat<'a, '_> {
+        let positions = &self.suffix_array()[lb..=rb];
+        Repeat {
+            substring: &self.suffix(positions[0])[..len as usize],
+            positions,
+        }
+    }
+
+    // characters preceding the suffixes in suffix array order
+    fn left_chars(&self) -> Vec<u32> {
+        self.suffix_array()
+            .iter()
+            .map(|&p| {
+                self.prefix(p)
+                    .chars()
+                    .next_back()
+                    .map_or(NO_CHAR, |c| c as u32)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::to_small_alphabet;
+    use crate::{concat_docs, IndexBuilder, MultiDocIndexBuilder};
+    use itertools::Itertools;
+    use quickcheck::TestResult;
+    use std::collections::BTreeMap;
+
+    // substring -> sorted positions of all substrings occurring at least twice
+    fn repeats_naive(text: &str) -> BTreeMap<&str, Vec<u32>> {
+        let mut repeats = BTreeMap::new();
+        let boundaries = text
+            .char_indices()
+            .map(|(i, _)| i)
+            .chain(std::iter::once(text.len()))
+            .collect::<Vec<_>>();
+        for (i, &begin) in boundaries.iter().enumerate() {
+            for &end in &boundaries[i + 1..] {
+                repeats
+                    .entry(&text[begin..end])
+                    .or_insert_with(Vec::new)
+                    .push(begin as u32);
+            }
+        }
+        repeats.retain(|_, v| v.len() >= 2);
+        repeats
+    }
+
+    fn sorted<'a>(repeats: Vec<crate::Repeat<'a, '_>>) -> Vec<(&'a str, Vec<u32>)> {
+        repeats
+            .into_iter()
+            .map(|r| (r.substring, r.positions.iter().copied().sorted().collect()))
+            .sorted()
+            .collect()
+    }
+
+    #[quickcheck]
+    fn repeats(text: String) -> TestResult {
+        if text.chars().count() > 30 {
+            return TestResult::discard();
+        }
+        let text = to_small_alphabet(&text, "ab_");
+        let index = IndexBuilder::new(&text).build().unwrap();
+        let naive = repeats_naive(&text);
+
+        let extends = |s: &str, ps: &[u32], left: bool| {
+            let next = |&p: &u32| {
+                if left {
+                    text[..p as usize].chars().next_back()
+                } else {
+                    text[p as usize + s.len()..].chars().next()
+                }
+            };
+            let first = next(&ps[0]);
+            first.is_some() && ps.iter().all(|p| next(p) == first)
+        };
+        let maximal = naive
+            .iter()
+            .filter(|(s, ps)| !extends(s, ps, true) && !extends(s, ps, false))
+            .map(|(s, ps)| (*s, ps.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(sorted(index.maximal_repeats(0, 0)), maximal);
+
+        let supermaximal = maximal
+            .iter()
+            .filter(|(s, _)| !naive.keys().any(|t| t.len() > s.len() && t.contains(s)))
+            .cloned()
+            .collect::<Vec<_>>();
+        assert_eq!(sorted(index.supermaximal_repeats(0, 0)), supermaximal);
+
+        let longest = index.longest_repeated_substring();
+        let max_len = naive.keys().map(|s| s.len()).max();
+        assert_eq!(longest.as_ref().map(|r| r.substring.len()), max_len);
+        if let Some(r) = longest {
+            assert_eq!(
+                r.positions.iter().copied().sorted().collect::<Vec<_>>(),
+                naive[r.substring]
+            );
+        }
+
+        TestResult::passed()
+    }
+
+    #[quickcheck]
+    fn generalized(docs: Vec<String>) -> TestResult {
+        let docs = docs
+            .iter()
+            .map(|doc| to_small_alphabet(doc, "ab_"))
+            .collect::<Vec<_>>();
+        let (text, offsets) = concat_docs(&docs);
+        if text.chars().count() > 30 {
+            return TestResult::discard();
+        }
+        let multi_doc_index = MultiDocIndexBuilder::new(&text)
+            .doc_offsets(offsets.clone())
+            .generalized(true)
+            .build()
+            .unwrap();
+        let index = multi_doc_index.index();
+
+        // substrings within documents, whose starts and ends differ from
+        // each other
+        let mut naive = BTreeMap::new();
+        for (doc, &offset) in docs.iter().zip(&offsets) {
+            let boundaries = doc
+                .char_indices()
+                .map(|(i, _)| i)
+                .chain(std::iter::once(doc.len()))
+                .collect::<Vec<_>>();
+            for (i, &begin) in boundaries.iter().enumerate() {
+                for &end in &boundaries[i + 1..] {
+                    naive
+                        .entry(&doc[begin..end])
+                        .or_insert_with(Vec::new)
+                        .push(offset + begin as u32);
+                }
+            }
+        }
+        naive.retain(|_, ps| ps.len() >= 2);
+
+        let extends = |s: &str, ps: &[u32], left: bool| {
+            let next = |&p: &u32| {
+                let doc_id = offsets.partition_point(|&offset| offset <= p) - 1;
+                let (doc, p) = (&docs[doc_id], (p - offsets[doc_id]) as usize);
+                if left {
+                    doc[..p].chars().next_back()
+                } else {
+                    doc[p + s.len()..].chars().next()
+                }
+            };
+            let first = next(&ps[0]);
+            first.is_some() && ps.iter().all(|p| next(p) == first)
+        };
+        let maximal = naive
+            .iter()
+            .filter(|(s, ps)| !extends(s, ps, true) && !extends(s, ps, false))
+            .map(|(s, ps)| (*s, ps.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(sorted(index.maximal_repeats(0, 0)), maximal);
+
+        TestResult::passed()
+    }
+
+    #[test]
+    fn thresholds() {
+        let text = "to be or not to be, to be is to do";
+        let index = IndexBuilder::new(text).build().unwrap();
+
+        let longest = index.longest_repeated_substring().unwrap();
+        assert_eq!(longest.substring, " to be");
+        assert_eq!(longest.positions.len(), 2);
+
+        let repeats = sorted(index.maximal_repeats(3, 3));
+        assert_eq!(
+            repeats,
+            [
+                (" to ", vec![12, 19, 28]),
+                ("to ", vec![0, 13, 20, 29]),
+                ("to be", vec![0, 13, 20])
+            ]
+        );
+
+        let repeats = sorted(index.supermaximal_repeats(3, 2));
+        assert_eq!(repeats, [(" to be", vec![12, 19]), ("to be ", vec![0, 20])]);
+    }
+}
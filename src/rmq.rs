@@ -0,0 +1,227 @@
+// Segment tree answering range minimum queries over an array of u32.
+#[derive(Clone)]
+pub(crate) struct MinTree {
+    len: usize,
+    tree: Vec<u32>,
+}
+
+impl MinTree {
+    pub fn new(values: &[u32]) -> Self {
+        let len = values.len().next_power_of_two();
+        let mut tree = vec![u32::MAX; 2 * len];
+        tree[len..len + values.len()].copy_from_slice(values);
+        for i in (1..len).rev() {
+            tree[i] = tree[2 * i].min(tree[2 * i + 1]);
+        }
+        Self { len, tree }
+    }
+
+    // Returns the largest position `j <= i` with a value less than `x`.
+    pub fn prev_less(&self, i: usize, x: u32) -> Option<usize> {
+        let mut node = i + self.len;
+        if self.tree[node] < x {
+            return Some(i);
+        }
+        // climb until a left sibling contains a smaller value
+        loop {
+            if node == 1 {
+                return None;
+            }
+            if node & 1 == 1 && self.tree[node - 1] < x {
+                node -= 1;
+                break;
+            }
+            node /= 2;
+        }
+        while node < self.len {
+            node = if self.tree[2 * node + 1] < x {
+                2 * node + 1
+            } else {
+                2 * node
+            };
+        }
+        Some(node - self.len)
+    }
+
+    // Returns the smallest position `j >= i` with a value less than `x`.
+    pub fn next_less(&self, i: usize, x: u32) -> Option<usize> {
+        if i >= self.len {
+            return None;
+        }
+        let mut node = i + self.len;
+        if self.tree[node] < x {
+            return Some(i);
+        }
+        loop {
+            if node == 1 {
+                return None;
+            }
+            if node & 1 == 0 && self.tree[node + 1] < x {
+                node += 1;
+                break;
+            }
+            node /= 2;
+        }
+        while node < self.len {
+            node = if self.tree[2 * node] < x {
+                2 * node
+            } else {
+                2 * node + 1
+            };
+        }
+        Some(node - self.len)
+    }
+}
+
+// number of values summarized by each minimum of the next level of BlockMins
+const FANOUT: usize = 64;
+
+// Minima of blocks of an array of u32, stored along with the array in levels
+// of decreasing length, where each minimum of a level is the minimum of
+// FANOUT consecutive values of the previous level. Unlike MinTree, the levels
+// can be built by a single pass over the array and take about 1/63 of it.
+pub(crate) struct BlockMins<'v> {
+    values: &'v [u32],
+    levels: Vec<&'v [u32]>,
+}
+
+impl<'v> BlockMins<'v> {
+    // `levels` are the concatenated levels written by BlockMinsBuilder.
+    pub fn new(values: &'v [u32], mut levels: &'v [u32]) -> Self {
+        let mut split = Vec::new();
+        for len in level_lens(values.len()) {
+            let (level, rest) = levels.split_at(len);
+            split.push(level);
+            levels = rest;
+        }
+        Self {
+            values,
+            levels: split,
+        }
+    }
+
+    // Returns the smallest position `j >= i` with a value less than `x`.
+    pub fn next_less(&self, i: usize, x: u32) -> Option<usize> {
+        let level = |l: usize| {
+            if l == 0 {
+                self.values
+            } else {
+                self.levels[l - 1]
+            }
+        };
+
+        // climb until the rest of a block contains a smaller value
+        let (mut l, mut pos) = (0, i);
+        loop {
+            let values = level(l);
+            let block_end = ((pos / FANOUT + 1) * FANOUT).min(values.len());
+            if let Some(j) = (pos..block_end).find(|&j| values[j] < x) {
+                pos = j;
+                break;
+            }
+            l += 1;
+            pos = pos / FANOUT + 1;
+            if l > self.levels.len() || pos >= level(l).len() {
+                return None;
+            }
+        }
+        // descend to the first smaller value of the block
+        while l > 0 {
+            l -= 1;
+            let values = level(l);
+            let begin = pos * FANOUT;
+            let end = (begin + FANOUT).min(values.len());
+            pos = (begin..end).find(|&j| values[j] < x).unwrap();
+        }
+        Some(pos)
+    }
+}
+
+// Returns the lengths of the levels of BlockMins over `len` values.
+pub(crate) fn level_lens(mut len: usize) -> Vec<usize> {
+    let mut lens = Vec::new();
+    while len > 1 {
+        len = len.div_ceil(FANOUT);
+        lens.push(len);
+    }
+    lens
+}
+
+// Builds the levels of BlockMins from values pushed in order.
+#[derive(Default)]
+pub(crate) struct BlockMinsBuilder {
+    len: usize,
+    first: Vec<u32>,
+}
+
+impl BlockMinsBuilder {
+    pub fn push(&mut self, x: u32) {
+        if self.len.is_multiple_of(FANOUT) {
+            self.first.push(x);
+        } else {
+            let min = self.first.last_mut().unwrap();
+            *min = (*min).min(x);
+        }
+        self.len += 1;
+    }
+
+    // Returns the concatenated levels.
+    pub fn finish(self) -> Vec<u32> {
+        let lens = level_lens(self.len);
+        if lens.is_empty() {
+            return Vec::new();
+        }
+        let mut levels = self.first;
+        let mut begin = 0;
+        for &len in &lens[..lens.len() - 1] {
+            let next = levels[begin..begin + len]
+                .chunks(FANOUT)
+                .map(|chunk| *chunk.iter().min().unwrap())
+                .collect::<Vec<_>>();
+            levels.extend(next);
+            begin += len;
+        }
+        levels
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BlockMins, BlockMinsBuilder, MinTree};
+
+    #[quickcheck]
+    fn queries(values: Vec<u32>, i: usize, x: u32) {
+        if values.is_empty() {
+            return;
+        }
+        let values = values.iter().map(|v| v % 8).collect::<Vec<_>>();
+        let (i, x) = (i % values.len(), x % 9);
+        let tree = MinTree::new(&values);
+
+        let expected = (0..=i).rev().find(|&k| values[k] < x);
+        assert_eq!(tree.prev_less(i, x), expected);
+        let expected = (i..values.len()).find(|&k| values[k] < x);
+        assert_eq!(tree.next_less(i, x), expected);
+    }
+
+    #[quickcheck]
+    fn block_mins(values: Vec<u32>, len: usize, i: usize, x: u32) {
+        // also spans several levels
+        let values = values
+            .iter()
+            .cycle()
+            .take(if values.is_empty() { 0 } else { len % 10000 })
+            .map(|v| v % 64)
+            .collect::<Vec<_>>();
+        let mut builder = BlockMinsBuilder::default();
+        for &v in &values {
+            builder.push(v);
+        }
+        let levels = builder.finish();
+        let mins = BlockMins::new(&values, &levels);
+
+        let x = x % 65;
+        let expected = (i % (values.len() + 1)..values.len()).find(|&k| values[k] < x);
+        assert_eq!(mins.next_less(i % (values.len() + 1), x), expected);
+    }
+}
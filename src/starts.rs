@@ -0,0 +1,62 @@
+use std::fmt;
+use std::sync::Arc;
+
+type Predicate = Arc<dyn Fn(&str, usize) -> bool + Send + Sync>;
+
+/// Which suffixes are included in a suffix array.
+///
+/// With anything other than `All`, only occurrences starting at the selected
+/// positions are found by `Index::positions` and the other queries.
+#[derive(Clone, Default)]
+pub enum SuffixStarts {
+    /// Every character boundary.
+    #[default]
+    All,
+    /// Word characters (alphanumeric characters and `_`) not preceded by a
+    /// word character.
+    WordStarts,
+    /// The start of the text and positions preceded by whitespace.
+    AfterWhitespace,
+    /// The start of the text and positions preceded by `\n`.
+    LineStarts,
+    /// Positions `p` for which the predicate returns true given the text
+    /// and `p`.
+    Custom(Predicate),
+}
+
+impl SuffixStarts {
+    pub(crate) fn is_all(&self) -> bool {
+        matches!(self, SuffixStarts::All)
+    }
+
+    // `pos` must be at a character boundary.
+    pub(crate) fn accepts(&self, text: &str, pos: usize) -> bool {
+        let prev = || text[..pos].chars().next_back();
+        match self {
+            SuffixStarts::All => true,
+            SuffixStarts::WordStarts => {
+                text[pos..].chars().next().is_some_and(is_word_char)
+                    && !prev().is_some_and(is_word_char)
+            }
+            SuffixStarts::AfterWhitespace => prev().is_none_or(char::is_whitespace),
+            SuffixStarts::LineStarts => prev().is_none_or(|c| c == '\n'),
+            SuffixStarts::Custom(f) => f(text, pos),
+        }
+    }
+}
+
+impl fmt::Debug for SuffixStarts {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SuffixStarts::All => write!(f, "All"),
+            SuffixStarts::WordStarts => write!(f, "WordStarts"),
+            SuffixStarts::AfterWhitespace => write!(f, "AfterWhitespace"),
+            SuffixStarts::LineStarts => write!(f, "LineStarts"),
+            SuffixStarts::Custom(_) => write!(f, "Custom(..)"),
+        }
+    }
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
@@ -0,0 +1,223 @@
+use crate::{Index, MultiDocIndex};
+use std::mem;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Stats {
+    /// Length of the text in bytes.
+    pub text_len: usize,
+    pub char_count: usize,
+    /// Distinct characters in ascending order.
+    pub alphabet: Vec<char>,
+    /// Number of distinct non-empty substrings, counted in characters. Only
+    /// substrings starting at positions in the suffix array are counted if
+    /// the index is sparse, and only those within documents if it is
+    /// generalized.
+    pub distinct_substrings: u64,
+    /// Longest common prefix of adjacent suffixes in bytes.
+    pub max_lcp: u32,
+    pub avg_lcp: f64,
+    /// Size of the suffix array in bytes.
+    pub suffix_array_size: usize,
+    /// Size of the inverse suffix array in bytes, or 0 if it is not stored.
+    pub inverse_suffix_array_size: usize,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct DocStats {
+    pub num_docs: usize,
+    /// Minimum, lower quartile, median, upper quartile and maximum of
+    /// document lengths in bytes.
+    pub doc_len_quartiles: [usize; 5],
+    pub avg_doc_len: f64,
+    /// Sizes in bytes of the sections of the serialized index.
+    pub offsets_size: usize,
+    pub delimiter_size: usize,
+    /// Includes the offsets of the metadata of each document.
+    pub meta_size: usize,
+    /// Size of the structures stored by `MultiDocIndexBuilder::doc_listing`.
+    pub listing_size: usize,
+    pub footer_size: usize,
+}
+
+impl<'a, 'b> Index<'a, 'b> {
+    pub fn stats(&self) -> Stats {
+        let text = self.text();
+        let sa = self.suffix_array();
+        let lcp = self.lcp_array();
+
+        let char_ranks = CharRanks::new(text);
+        let char_count = char_ranks.rank(text.len());
+
+        // each suffix contributes its prefixes longer than the common prefix
+        // with the preceding suffix
+        let distinct_substrings = sa
+            .iter()
+            .zip(&lcp)
+            .map(|(&p, &l)| {
+                let end = p as usize + self.suffix(p).len();
+                (char_ranks.rank(end) - char_ranks.rank((p + l) as usize)) as u64
+            })
+            .sum();
+
+        // a bit for each code point
+        let mut seen = vec![0u64; (char::MAX as usize + 1).div_ceil(64)];
+        for c in text.chars() {
+            seen[c as usize / 64] |= 1 << (c as usize % 64);
+        }
+        let alphabet = (0..=char::MAX as u32)
+            .filter(|&c| seen[c as usize / 64] & (1 << (c % 64)) != 0)
+            .filter_map(char::from_u32)
+            .collect();
+
+        Stats {
+            text_len: text.len(),
+            char_count: char_count as usize,
+            alphabet,
+            distinct_substrings,
+            max_lcp: lcp.iter().copied().max().unwrap_or(0),
+            avg_lcp: if lcp.len() > 1 {
+                lcp.iter().map(|&l| l as f64).sum::<f64>() / (lcp.len() - 1) as f64
+            } else {
+                0.0
+            },
+            suffix_array_size: mem::size_of_val(sa),
+            inverse_suffix_array_size: self.inverse_suffix_array().map_or(0, mem::size_of_val),
+        }
+    }
+}
+
+// number of bytes between samples of CharRanks
+const SAMPLE_INTERVAL: usize = 64;
+
+// Numbers of characters preceding byte positions, sampled at every
+// SAMPLE_INTERVAL bytes so as not to take a word per byte.
+struct CharRanks<'t> {
+    text: &'t [u8],
+    samples: Vec<u32>,
+}
+
+impl<'t> CharRanks<'t> {
+    fn new(text: &'t str) -> Self {
+        let text = text.as_bytes();
+        let mut samples = Vec::with_capacity(text.len() / SAMPLE_INTERVAL + 1);
+        let mut rank = 0;
+        samples.push(rank);
+        for chunk in text.chunks(SAMPLE_INTERVAL) {
+            rank += count_chars(chunk);
+            samples.push(rank);
+        }
+        Self { text, samples }
+    }
+
+    // Returns the number of characters preceding the byte position `i`.
+    fn rank(&self, i: usize) -> u32 {
+        let sample = i / SAMPLE_INTERVAL;
+        self.samples[sample] + count_chars(&self.text[sample * SAMPLE_INTERVAL..i])
+    }
+}
+
+fn count_chars(bytes: &[u8]) -> u32 {
+    // every byte other than continuation bytes starts a character
+    bytes.iter().filter(|&&b| b & 0xc0 != 0x80).count() as u32
+}
+
+impl<'a, 'b> MultiDocIndex<'a, 'b> {
+    pub fn stats(&self) -> (Stats, DocStats) {
+        let mut doc_lens = (0..self.num_docs() as u32)
+            .map(|doc_id| self.doc(doc_id).unwrap().len())
+            .collect::<Vec<_>>();
+        doc_lens.sort_unstable();
+        let quartile = |q: usize| doc_lens[(doc_lens.len() - 1) * q / 4];
+
+        let doc_stats = DocStats {
+            num_docs: doc_lens.len(),
+            doc_len_quartiles: if doc_lens.is_empty() {
+                [0; 5]
+            } else {
+                [
+                    quartile(0),
+                    quartile(1),
+                    quartile(2),
+                    quartile(3),
+                    quartile(4),
+                ]
+            },
+            avg_doc_len: if doc_lens.is_empty() {
+                0.0
+            } else {
+                doc_lens.iter().sum::<usize>() as f64 / doc_lens.len() as f64
+            },
+            offsets_size: mem::size_of::<u32>() * self.num_docs(),
+            delimiter_size: self.delimiter().len(),
+            meta_size: self.meta_size(),
+            listing_size: self.listing.as_ref().map_or(0, |listing| listing.size()),
+            footer_size: self.footer_size(),
+        };
+
+        (self.index().stats(), doc_stats)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::to_small_alphabet;
+    use crate::{IndexBuilder, MultiDocIndexBuilder, SuffixStarts};
+    use std::collections::HashSet;
+
+    #[quickcheck]
+    fn distinct_substrings(text: String, sparse: bool) {
+        let text = to_small_alphabet(&text, "ab_")
+            .chars()
+            .take(30)
+            .collect::<String>();
+        let starts = if sparse {
+            SuffixStarts::WordStarts
+        } else {
+            SuffixStarts::All
+        };
+        let index = IndexBuilder::new(&text)
+            .suffix_starts(starts.clone())
+            .build()
+            .unwrap();
+        let stats = index.stats();
+
+        let boundaries = text
+            .char_indices()
+            .map(|(i, _)| i)
+            .chain(std::iter::once(text.len()))
+            .collect::<Vec<_>>();
+        let mut substrings = HashSet::new();
+        for (i, &begin) in boundaries.iter().enumerate() {
+            if !starts.accepts(&text, begin) {
+                continue;
+            }
+            for &end in &boundaries[i + 1..] {
+                substrings.insert(&text[begin..end]);
+            }
+        }
+        assert_eq!(stats.distinct_substrings, substrings.len() as u64);
+        assert_eq!(stats.char_count, boundaries.len() - 1);
+        assert_eq!(
+            stats.alphabet.len(),
+            text.chars().collect::<HashSet<_>>().len()
+        );
+    }
+
+    #[test]
+    fn documents() {
+        let text = "banana\nbandana\n\nnab";
+        let multi_doc_index = MultiDocIndexBuilder::new(text).build().unwrap();
+        let (stats, doc_stats) = multi_doc_index.stats();
+
+        assert_eq!(stats.text_len, 19);
+        assert_eq!(stats.alphabet, ['\n', 'a', 'b', 'd', 'n']);
+        assert_eq!(stats.max_lcp, 4);
+        assert_eq!(stats.suffix_array_size, 19 * 4);
+        assert_eq!(doc_stats.num_docs, 4);
+        assert_eq!(doc_stats.doc_len_quartiles, [0, 0, 3, 6, 7]);
+        assert_eq!(doc_stats.avg_doc_len, 4.0);
+        assert_eq!(doc_stats.offsets_size, 16);
+        assert_eq!(doc_stats.meta_size, 0);
+        assert_eq!(doc_stats.listing_size, 0);
+    }
+}
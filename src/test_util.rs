@@ -0,0 +1,13 @@
+// Maps each character to the character of `alphabet` at its code point
+// modulo the size of the alphabet, where `_` keeps the character. Mapping
+// most characters to a few letters makes repeats likely in random texts,
+// while keeping some covers multibyte characters.
+pub(crate) fn to_small_alphabet(s: &str, alphabet: &str) -> String {
+    let alphabet = alphabet.chars().collect::<Vec<_>>();
+    s.chars()
+        .map(|c| match alphabet[c as usize % alphabet.len()] {
+            '_' => c,
+            letter => letter,
+        })
+        .collect()
+}
@@ -0,0 +1,280 @@
+use crate::Index;
+use std::ops::Range;
+
+const NONE: u32 = u32::MAX;
+
+/// Suffix tree simulated by the suffix array, LCP array and child table
+/// (Abouelhoda et al., "Replacing suffix trees with enhanced suffix arrays").
+pub struct SuffixTree<'a, 'i> {
+    index: &'i Index<'a, 'i>,
+    suffix_array: &'i [u32],
+    lcp: Vec<u32>,
+    up: Vec<u32>,
+    down: Vec<u32>,
+    next_l_index: Vec<u32>,
+}
+
+/// Node of a `SuffixTree`, i.e. an lcp-interval or a single suffix.
+///
+/// Suffixes that are prefixes of other suffixes are leaves with the same
+/// depth as their parents.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Node {
+    /// Range of the suffix array holding the leaves under the node.
+    pub range: Range<usize>,
+    /// Length of the path label in bytes.
+    pub depth: usize,
+}
+
+impl Node {
+    pub fn is_leaf(&self) -> bool {
+        self.range.len() == 1
+    }
+}
+
+impl<'a, 'b> Index<'a, 'b> {
+    pub fn suffix_tree(&self) -> SuffixTree<'a, '_> {
+        let lcp = self.lcp_array();
+        let n = lcp.len();
+        // lcp[i] with -1 at both ends
+        let l = |i: usize| {
+            if i == 0 || i == n {
+                -1
+            } else {
+                lcp[i] as i64
+            }
+        };
+
+        let mut up = vec![NONE; n + 1];
+        let mut down = vec![NONE; n];
+        let mut next_l_index = vec![NONE; n];
+
+        let mut stack = vec![0];
+        let mut last = NONE;
+        for (i, up_i) in up.iter_mut().enumerate().skip(1) {
+            while l(i) < l(*stack.last().unwrap()) {
+                last = stack.pop().unwrap() as u32;
+                let top = *stack.last().unwrap();
+                if l(i) <= l(top) && l(top) != l(last as usize) {
+                    down[top] = last;
+                }
+            }
+            if last != NONE {
+                *up_i = last;
+                last = NONE;
+            }
+            stack.push(i);
+        }
+
+        let mut stack = vec![0];
+        for i in 1..n {
+            while l(i) < l(*stack.last().unwrap()) {
+                stack.pop();
+            }
+            if l(i) == l(*stack.last().unwrap()) {
+                next_l_index[stack.pop().unwrap()] = i as u32;
+            }
+            stack.push(i);
+        }
+
+        SuffixTree {
+            index: self,
+            suffix_array: self.suffix_array(),
+            lcp,
+            up,
+            down,
+            next_l_index,
+        }
+    }
+}
+
+impl<'a, 'i> SuffixTree<'a, 'i> {
+    /// Returns the root, whose depth is the length of the prefix shared by
+    /// all suffixes.
+    pub fn root(&self) -> Node {
+        self.node(0..self.suffix_array.len())
+    }
+
+    pub fn children(&self, node: &Node) -> Vec<Node> {
+        if node.range.len() < 2 {
+            return Vec::new();
+        }
+        let mut children = Vec::new();
+        let mut begin = node.range.start;
+        let mut i = self.first_l_index(&node.range);
+        while i != NONE {
+            children.push(self.node(begin..i as usize));
+            begin = i as usize;
+            i = self.next_l_index[i as usize];
+        }
+        children.push(self.node(begin..node.range.end));
+        children
+    }
+
+    /// Returns the child whose edge label starts with `c`.
+    pub fn child(&self, node: &Node, c: char) -> Option<Node> {
+        self.children(node).into_iter().find(|child| {
+            let suffix = self.index.suffix(self.suffix_array[child.range.start]);
+            suffix[node.depth..].starts_with(c)
+        })
+    }
+
+    /// Returns the path label from the root to `node`.
+    pub fn label(&self, node: &Node) -> &'a str {
+        match self.suffix_array.get(node.range.start) {
+            Some(&p) => &self.index.text()[p as usize..p as usize + node.depth],
+            None => "",
+        }
+    }
+
+    /// Returns the positions of the leaves under `node` in suffix array
+    /// order.
+    pub fn positions(&self, node: &Node) -> &'i [u32] {
+        &self.suffix_array[node.range.clone()]
+    }
+
+    /// Returns an iterator over all nodes in depth-first preorder.
+    pub fn nodes(&self) -> Nodes<'_, 'a, 'i> {
+        Nodes {
+            tree: self,
+            stack: if self.suffix_array.is_empty() {
+                Vec::new()
+            } else {
+                vec![self.root()]
+            },
+        }
+    }
+
+    fn node(&self, range: Range<usize>) -> Node {
+        let depth = match range.len() {
+            0 => 0,
+            1 => self.index.suffix(self.suffix_array[range.start]).len(),
+            _ => self.lcp[self.first_l_index(&range) as usize] as usize,
+        };
+        Node { range, depth }
+    }
+
+    // first index in the interval where the lcp equals that of the interval
+    fn first_l_index(&self, range: &Range<usize>) -> u32 {
+        let up = self.up[range.end];
+        if range.start < up as usize && (up as usize) < range.end {
+            up
+        } else {
+            self.down[range.start]
+        }
+    }
+}
+
+/// Iterator returned by `SuffixTree::nodes`.
+pub struct Nodes<'t, 'a, 'i> {
+    tree: &'t SuffixTree<'a, 'i>,
+    stack: Vec<Node>,
+}
+
+impl<'t, 'a, 'i> Iterator for Nodes<'t, 'a, 'i> {
+    type Item = Node;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.stack.pop()?;
+        self.stack
+            .extend(self.tree.children(&node).into_iter().rev());
+        Some(node)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Node;
+    use crate::test_util::to_small_alphabet;
+    use crate::IndexBuilder;
+    use std::collections::BTreeSet;
+
+    #[quickcheck]
+    fn nodes(text: String) {
+        let text = to_small_alphabet(&text, "ab_");
+        let index = IndexBuilder::new(&text).build().unwrap();
+        let sa = index.suffix_array();
+        let tree = index.suffix_tree();
+
+        // internal nodes are right-branching substrings along with their
+        // suffix array ranges
+        let mut expected = BTreeSet::new();
+        for i in 0..sa.len() {
+            for j in i + 1..sa.len() {
+                let a = &text[sa[i] as usize..];
+                let b = &text[sa[j] as usize..];
+                let depth = a
+                    .char_indices()
+                    .map(|(k, c)| k + c.len_utf8())
+                    .take_while(|&k| b.starts_with(&a[..k]))
+                    .last()
+                    .unwrap_or(0);
+                let prefix = &a[..depth];
+                let begin = sa
+                    .iter()
+                    .position(|&p| text[p as usize..].starts_with(prefix));
+                let end = sa
+                    .iter()
+                    .rposition(|&p| text[p as usize..].starts_with(prefix));
+                if begin == Some(i) && end == Some(j) {
+                    expected.insert((i, j + 1, depth));
+                }
+            }
+        }
+
+        let nodes = tree.nodes().collect::<Vec<_>>();
+        let leaves = nodes.iter().filter(|n| n.is_leaf()).collect::<Vec<_>>();
+        assert_eq!(leaves.len(), sa.len());
+        for (i, leaf) in leaves.iter().enumerate() {
+            assert_eq!(leaf.range, i..i + 1);
+            assert_eq!(tree.label(leaf), &text[sa[i] as usize..]);
+        }
+
+        let internal = nodes
+            .iter()
+            .filter(|n| !n.is_leaf())
+            .map(|n| (n.range.start, n.range.end, n.depth))
+            .collect::<BTreeSet<_>>();
+        assert_eq!(internal, expected);
+
+        for node in &nodes {
+            let label = tree.label(node);
+            for &p in tree.positions(node) {
+                assert!(text[p as usize..].starts_with(label));
+            }
+            for child in tree.children(node) {
+                assert!(child.depth >= node.depth);
+                let c = tree.label(&child)[node.depth..].chars().next();
+                if let Some(c) = c {
+                    assert_eq!(tree.child(node, c), Some(child));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn traversal() {
+        let index = IndexBuilder::new("banana").build().unwrap();
+        let tree = index.suffix_tree();
+        let root = tree.root();
+        assert_eq!(
+            root,
+            Node {
+                range: 0..6,
+                depth: 0
+            }
+        );
+
+        let ana = tree.child(&root, 'a').unwrap();
+        assert_eq!(tree.label(&ana), "a");
+        let ana = tree.child(&ana, 'n').unwrap();
+        assert_eq!(tree.label(&ana), "ana");
+        let mut positions = tree.positions(&ana).to_vec();
+        positions.sort_unstable();
+        assert_eq!(positions, [1, 3]);
+        assert!(tree.child(&root, 'x').is_none());
+
+        let empty = IndexBuilder::new("").build().unwrap();
+        assert_eq!(empty.suffix_tree().nodes().count(), 0);
+    }
+}
@@ -0,0 +1,277 @@
+use crate::{Index, Result};
+use std::iter::Peekable;
+use std::str::Chars;
+
+// upper bound of n in `{n}`, as the preceding element is copied n times
+const MAX_REPETITION: usize = 1 << 16;
+
+#[derive(Clone, Debug, PartialEq)]
+struct CharClass {
+    ranges: Vec<(char, char)>,
+    negated: bool,
+}
+
+impl CharClass {
+    fn any() -> Self {
+        Self {
+            ranges: Vec::new(),
+            negated: true,
+        }
+    }
+
+    fn contains(&self, c: char) -> bool {
+        self.ranges.iter().any(|&(lo, hi)| lo <= c && c <= hi) != self.negated
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Element {
+    Literal(String),
+    Class(CharClass),
+}
+
+/*
+   Pattern syntax:
+       ?       any single character
+       [abc]   character class, e.g. [A-Z0-9_], negated with [!...] or [^...]
+       \d      digit
+       \w      word character
+       \s      whitespace
+       {n}     exactly n repetitions of the preceding character or class,
+               where n is at most MAX_REPETITION
+       \x      escaped character x
+*/
+fn parse(pattern: &str) -> Result<Vec<Element>> {
+    let mut elements = Vec::new();
+    let mut chars = pattern.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        let element = match c {
+            '?' => Element::Class(CharClass::any()),
+            '[' => Element::Class(parse_class(&mut chars)?),
+            '\\' => parse_escape(&mut chars)?,
+            '{' => {
+                let n = parse_repetition(&mut chars)?;
+                let prev = elements
+                    .pop()
+                    .ok_or_else(|| invalid_pattern("nothing to repeat"))?;
+                elements.extend(std::iter::repeat_n(prev, n));
+                continue;
+            }
+            '*' => return Err(invalid_pattern("variable-length wildcard is not supported")),
+            ']' | '}' => return Err(invalid_pattern(&format!("unmatched '{}'", c))),
+            c => Element::Literal(c.to_string()),
+        };
+        elements.push(element);
+    }
+
+    // merge runs of literals so that they are looked up at once
+    let mut merged: Vec<Element> = Vec::with_capacity(elements.len());
+    for element in elements {
+        match (merged.last_mut(), element) {
+            (Some(Element::Literal(s)), Element::Literal(t)) => s.push_str(&t),
+            (_, element) => merged.push(element),
+        }
+    }
+
+    Ok(merged)
+}
+
+fn parse_escape(chars: &mut Peekable<Chars>) -> Result<Element> {
+    let class = |ranges: &[(char, char)]| {
+        Element::Class(CharClass {
+            ranges: ranges.to_vec(),
+            negated: false,
+        })
+    };
+    match chars.next() {
+        Some('d') => Ok(class(&[('0', '9')])),
+        Some('w') => Ok(class(&[('0', '9'), ('A', 'Z'), ('_', '_'), ('a', 'z')])),
+        Some('s') => Ok(class(&[('\t', '\r'), (' ', ' ')])),
+        Some(c) => Ok(Element::Literal(c.to_string())),
+        None => Err(invalid_pattern("trailing backslash")),
+    }
+}
+
+fn parse_class(chars: &mut Peekable<Chars>) -> Result<CharClass> {
+    let negated = chars.next_if(|&c| c == '!' || c == '^').is_some();
+    let mut ranges = Vec::new();
+
+    loop {
+        let lo = match chars.next() {
+            Some(']') if !ranges.is_empty() => break,
+            Some('\\') => chars
+                .next()
+                .ok_or_else(|| invalid_pattern("trailing backslash"))?,
+            Some(c) => c,
+            None => return Err(invalid_pattern("unclosed character class")),
+        };
+        let hi = if chars.next_if_eq(&'-').is_some() {
+            match chars.next() {
+                Some(']') => {
+                    ranges.push((lo, lo));
+                    ranges.push(('-', '-'));
+                    break;
+                }
+                Some('\\') => chars
+                    .next()
+                    .ok_or_else(|| invalid_pattern("trailing backslash"))?,
+                Some(c) => c,
+                None => return Err(invalid_pattern("unclosed character class")),
+            }
+        } else {
+            lo
+        };
+        if lo > hi {
+            return Err(invalid_pattern(&format!("invalid range {}-{}", lo, hi)));
+        }
+        ranges.push((lo, hi));
+    }
+
+    Ok(CharClass { ranges, negated })
+}
+
+fn parse_repetition(chars: &mut Peekable<Chars>) -> Result<usize> {
+    let mut digits = String::new();
+    loop {
+        match chars.next() {
+            Some('}') => break,
+            Some(c) => digits.push(c),
+            None => return Err(invalid_pattern("unclosed repetition")),
+        }
+    }
+    let n = digits
+        .parse()
+        .map_err(|_| invalid_pattern(&format!("invalid repetition {{{}}}", digits)))?;
+    if n > MAX_REPETITION {
+        return Err(invalid_pattern(&format!(
+            "repetition {{{}}} exceeds the limit of {}",
+            n, MAX_REPETITION
+        )));
+    }
+    Ok(n)
+}
+
+fn invalid_pattern(msg: &str) -> crate::Error {
+    crate::Error::InvalidPattern(msg.to_string())
+}
+
+impl<'a, 'b> Index<'a, 'b> {
+    /// Returns `(position, length)` of matches of a fixed-length pattern with
+    /// `?` wildcards and character classes, sorted by position.
+    pub fn wildcard_positions(&self, pattern: &str) -> Result<Vec<(u32, u32)>> {
+        let elements = parse(pattern)?;
+        let mut matches = Vec::new();
+        if elements.is_empty() {
+            return Ok(matches);
+        }
+
+        // explicit stack of suffix ranges along with their depths and the
+        // numbers of elements matched, as a pattern can have many elements
+        let mut stack = vec![(0..self.suffix_array().len(), 0, 0)];
+        while let Some((range, depth, i)) = stack.pop() {
+            if range.is_empty() {
+                continue;
+            }
+            match elements.get(i) {
+                None => {
+                    let sa = &self.suffix_array()[range];
+                    matches.extend(sa.iter().map(|&p| (p, depth as u32)));
+                }
+                Some(Element::Literal(s)) => {
+                    let range = self.narrow(range, depth, s);
+                    stack.push((range, depth + s.len(), i + 1));
+                }
+                Some(Element::Class(class)) => {
+                    for (c, child) in self.children(range, depth) {
+                        if class.contains(c) {
+                            stack.push((child, depth + c.len_utf8(), i + 1));
+                        }
+                    }
+                }
+            }
+        }
+        matches.sort_unstable();
+        Ok(matches)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse, Element};
+    use crate::test_util::to_small_alphabet;
+    use crate::IndexBuilder;
+
+    const PATTERNS: &[&str] = &[
+        "a", "a?", "?b?", "[ab]c", "[!a]{2}", "a?{2}c", r"\w\d", "[a-c]{3}", r"\??",
+    ];
+
+    fn matches_naive(text: &str, elements: &[Element]) -> Option<usize> {
+        let mut len = 0;
+        let mut chars = text.chars();
+        for element in elements {
+            match element {
+                Element::Literal(s) => {
+                    if !text[len..].starts_with(s.as_str()) {
+                        return None;
+                    }
+                    len += s.len();
+                    chars = text[len..].chars();
+                }
+                Element::Class(class) => {
+                    let c = chars.next().filter(|&c| class.contains(c))?;
+                    len += c.len_utf8();
+                }
+            }
+        }
+        Some(len)
+    }
+
+    #[quickcheck]
+    fn wildcard_positions(text: String) {
+        let text = to_small_alphabet(&text, "abc1_");
+        let index = IndexBuilder::new(&text).build().unwrap();
+
+        for pattern in PATTERNS {
+            let elements = parse(pattern).unwrap();
+            let expected = (0..text.len())
+                .filter(|&i| text.is_char_boundary(i))
+                .filter_map(|i| matches_naive(&text[i..], &elements).map(|l| (i as u32, l as u32)))
+                .collect::<Vec<_>>();
+            assert_eq!(index.wildcard_positions(pattern).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn fixed_length_ids() {
+        let text = "ID-1234-2020 ID-12a4-2020 ID-9999-2021 XYZ5 xyz5 AB7";
+        let index = IndexBuilder::new(text).build().unwrap();
+        assert_eq!(
+            index.wildcard_positions("ID-????-2020").unwrap(),
+            [(0, 12), (13, 12)]
+        );
+        assert_eq!(index.wildcard_positions(r"[A-Z]{3}\d").unwrap(), [(39, 4)]);
+        assert_eq!(
+            index.wildcard_positions(r"ID-\d\d\d\d").unwrap(),
+            [(0, 7), (26, 7)]
+        );
+    }
+
+    #[test]
+    fn invalid_pattern() {
+        let index = IndexBuilder::new("abc").build().unwrap();
+        for pattern in &["[ab", "a*", "{2}", "a{x}", "[z-a]", "\\", "a{100000}"] {
+            assert!(index.wildcard_positions(pattern).is_err());
+        }
+    }
+
+    #[test]
+    fn many_elements() {
+        // one element per character, which used to recurse once each
+        let text = "a".repeat(70000);
+        let index = IndexBuilder::new(&text).build().unwrap();
+        let matches = index.wildcard_positions("?{65536}").unwrap();
+        assert_eq!(matches.len(), 70000 - 65536 + 1);
+        assert!(matches.iter().all(|&(_, len)| len == 65536));
+    }
+}